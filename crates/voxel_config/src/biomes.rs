@@ -0,0 +1,192 @@
+//= IMPORTS ========================================================================================
+
+use nanoserde::{DeJson, SerJson};
+
+use std::fs::{read_to_string, write};
+
+//= BIOMES CONFIG ==================================================================================
+
+/// Object with the biome definitions used by the world generation.
+///
+/// Voxels are referenced by their display name (e.g. `"Oak Leaves"`).
+#[derive(Debug, Clone, DeJson, SerJson)]
+pub struct BiomesConfig {
+    /// Climate distance, in temperature and moisture units, over which two adjacent biomes
+    /// are dithered into each other.
+    pub blend: f32,
+    /// Trees that the vegetation rules can refer to by name.
+    pub trees: Vec<TreeConfig>,
+    /// Schematic files that the vegetation rules can refer to by name.
+    #[nserde(default)]
+    pub schematics: Vec<SchematicConfig>,
+    /// The first biome whose climate ranges contain the climate of a column is chosen,
+    /// the last one is used when none does.
+    pub biomes: Vec<BiomeConfig>,
+}
+
+#[derive(Debug, Clone, DeJson, SerJson)]
+pub struct BiomeConfig {
+    pub name: String,
+    /// Voxel on top of the column.
+    pub surface: String,
+    /// Layers right below the surface, from the top, the stone starts below them.
+    pub layers: Vec<LayerConfig>,
+    /// Temperature range, from min inclusive to max exclusive, between 0 and 1.
+    pub temperature: [f32; 2],
+    /// Moisture range, from min inclusive to max exclusive, between 0 and 1.
+    pub moisture: [f32; 2],
+    /// Rules tried in order for every column, the first one that succeeds spawns its structure.
+    pub vegetation: Vec<VegetationConfig>,
+}
+
+#[derive(Debug, Clone, DeJson, SerJson)]
+pub struct LayerConfig {
+    pub voxel: String,
+    /// At least one voxel.
+    pub thickness: u32,
+}
+
+#[derive(Debug, Clone, DeJson, SerJson)]
+pub struct VegetationConfig {
+    /// Name of a tree in `BiomesConfig::trees`, of a schematic in `BiomesConfig::schematics`,
+    /// or one of the built-in `"cactus"` and `"spruce"` structures.
+    pub structure: String,
+    /// Chance for a column to spawn the structure, scaled by the vegetation noise.
+    pub chance: f32,
+}
+
+#[derive(Debug, Clone, DeJson, SerJson)]
+pub struct TreeConfig {
+    pub name: String,
+    /// Trunk height range, from min inclusive to max exclusive.
+    pub height: [u32; 2],
+    pub bark: String,
+    pub leaves: String,
+    /// Chance of every leaf to be left out.
+    pub leaves_decay: f32,
+    /// Only trees taller than 10 voxels grow branches.
+    pub branch_count: [u32; 2],
+    /// Height of the branches relative to the trunk height, from 0 to 1.
+    pub branch_height: [f32; 2],
+    /// Length of the branches, up to 13 voxels so their leaves fit the margin of the chunks.
+    pub branch_len: [f32; 2],
+}
+
+#[derive(Debug, Clone, DeJson, SerJson)]
+pub struct SchematicConfig {
+    pub name: String,
+    /// Path of the schematic file, saved by `Schematic::save`.
+    pub file: String,
+    /// Height of the schematic bottom relative to the surface voxel of its column,
+    /// 1 puts it right above the ground. Horizontally it's centered on the column.
+    pub offset_y: i32,
+}
+
+impl BiomesConfig {
+    //- Load ---------------------------------------------------------------------------------------
+
+    /// Loads a biomes file.
+    /// Returns a biomes object or error otherwise.
+    pub(crate) fn load(filename: &str) -> Result<Self, String> {
+        let filepath = filename;
+
+        let contents = match read_to_string(filepath) {
+            Ok(c) => c,
+            Err(e) => return Err(format!("{e}: {filepath}")),
+        };
+
+        match DeJson::deserialize_json(contents.as_str()) {
+            Ok(c) => Ok(c),
+            Err(e) => Err(format!("{e}: {filepath}")),
+        }
+    }
+
+    /// Loads a biomes file.
+    ///
+    /// If the file is not found then the built-in biomes are returned.
+    #[must_use]
+    pub fn load_or_default(filename: &str) -> Self {
+        Self::load(filename).unwrap_or_else(|e| {
+            log::error!("{e:?}");
+            Self::default()
+        })
+    }
+
+    //- Save ---------------------------------------------------------------------------------------
+
+    /// Saves a biomes file.
+    /// An error is returned if something went wrong.
+    #[allow(dead_code)]
+    pub(crate) fn save(&self, filename: &str) -> Result<(), String> {
+        let filepath = filename;
+        let contents = SerJson::serialize_json(self);
+        match write(filepath, contents) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(format!("{e}: {filepath}")),
+        }
+    }
+}
+
+impl Default for BiomesConfig {
+    fn default() -> Self {
+        let dirt = || {
+            vec![LayerConfig {
+                voxel: "Dirt".into(),
+                thickness: 3,
+            }]
+        };
+        let biome = |name: &str, surface: &str, temperature, moisture, vegetation| BiomeConfig {
+            name: name.into(),
+            surface: surface.into(),
+            layers: dirt(),
+            temperature,
+            moisture,
+            vegetation,
+        };
+        let rule = |structure: &str, chance| VegetationConfig {
+            structure: structure.into(),
+            chance,
+        };
+        let tree = |name: &str, height, wood: &str| TreeConfig {
+            name: name.into(),
+            height,
+            bark: format!("{wood} Wood"),
+            leaves: format!("{wood} Leaves"),
+            leaves_decay: 0.1,
+            branch_count: [1, 4],
+            branch_height: [0.5, 0.8],
+            branch_len: [3.0, 8.0],
+        };
+
+        Self {
+            blend: 0.03,
+            trees: vec![tree("oak", [6, 19], "Oak"), tree("birch", [9, 26], "Birch")],
+            schematics: vec![],
+            biomes: vec![
+                biome(
+                    "Desert",
+                    "Sand",
+                    [0.7, 1.0],
+                    [0.0, 0.3],
+                    vec![rule("cactus", 0.01)],
+                ),
+                biome("Tundra", "Dead Grass", [0.0, 0.3], [0.0, 0.3], vec![]),
+                biome(
+                    "Taiga",
+                    "Snow",
+                    [0.0, 0.3],
+                    [0.3, 1.0],
+                    vec![rule("spruce", 0.003)],
+                ),
+                biome("Swamp", "Moist Grass", [0.7, 1.0], [0.7, 1.0], vec![]),
+                biome(
+                    "Forest",
+                    "Grass",
+                    [0.0, 1.0],
+                    [0.0, 1.0],
+                    vec![rule("oak", 0.0025), rule("birch", 0.0025)],
+                ),
+            ],
+        }
+    }
+}
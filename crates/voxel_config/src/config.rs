@@ -24,6 +24,26 @@ pub struct Config {
     /// The window starts maximised and fullscreen-borderless otherwise
     /// windowed with decorations.
     pub maximized: bool,
+    /// Threads generating the world, zero uses one per CPU core.
+    #[nserde(default)]
+    pub generation_threads: u16,
+    /// Shares the identical subtrees of the world, see `World::deduplicate`.
+    #[nserde(default)]
+    pub deduplicate_world: bool,
+    /// The world is `2^world_depth` voxels per side.
+    #[nserde(default = "9")]
+    pub world_depth: u32,
+    /// Position of the world min corner when the game starts,
+    /// the world must reach the sea level, the height 26.
+    #[nserde(default)]
+    pub world_origin: [i32; 3],
+    /// X and Z of the player spawn from the centre of the world,
+    /// the player always spawns on the surface.
+    #[nserde(default)]
+    pub spawn_offset: [i32; 2],
+    /// Passes of the denoiser, each one blurs twice as far as the previous.
+    #[nserde(default = "4")]
+    pub denoiser_iterations: u32,
 }
 
 impl Config {
@@ -78,6 +98,12 @@ impl Default for Config {
             surface_width: MIN_WIDTH,
             surface_height: MIN_HEIGHT,
             maximized: false,
+            generation_threads: 0,
+            deduplicate_world: false,
+            world_depth: 9,
+            world_origin: [0; 3],
+            spawn_offset: [0; 2],
+            denoiser_iterations: 4,
         }
     }
 }
@@ -1,7 +1,9 @@
 //= MODS ===========================================================================================
 
+mod biomes;
 mod config;
 
 //= RE-EXPORTS =====================================================================================
 
+pub use crate::biomes::*;
 pub use crate::config::*;
@@ -35,6 +35,11 @@ pub struct BitField(u32);
 impl BitField {
     pub const ZERO: Self = Self(0);
 
+    #[must_use]
+    pub const fn from_raw(raw: u32) -> Self {
+        Self(raw)
+    }
+
     #[must_use]
     pub const fn raw(self) -> u32 {
         self.0
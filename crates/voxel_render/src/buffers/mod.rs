@@ -47,24 +47,16 @@ impl<T> SimpleBuffer<T> {
     }
 }
 
-impl<T, const N: usize> SimpleBuffer<[T; N]> {
-    pub(crate) fn write_slice(&self, queue: &Queue, idx: u64, value: &[T]) {
-        let ptr = value.as_ptr().cast::<u8>();
-        let size = size_of_val(value);
-        #[allow(unsafe_code)]
-        let slice = unsafe { slice::from_raw_parts(ptr, size) };
-        queue.write_buffer(&self.0, idx, slice);
-    }
-}
-
 //= BUFFERS ========================================================================================
 
 pub struct Buffers {
     pub camera_buffer: SimpleBuffer<Camera>,
+    /// The camera of the previous frame, to reproject the accumulated samples.
+    pub prev_camera_buffer: SimpleBuffer<Camera>,
     pub settings: SimpleBuffer<Settings>,
     pub world_data: SimpleBuffer<WorldData>,
     pub nodes: NodesBuffer,
-    pub voxel_materials: SimpleBuffer<[Material; 256]>,
+    pub voxel_materials: MaterialsBuffer,
     pub frame_count: SimpleBuffer<FrameData>,
 }
 
@@ -76,10 +68,11 @@ impl Buffers {
 
         Self {
             camera_buffer: SimpleBuffer::new(device, "", COPY_DST | UNIFORM),
+            prev_camera_buffer: SimpleBuffer::new(device, "", COPY_DST | UNIFORM),
             settings: SimpleBuffer::new(device, "", COPY_DST | UNIFORM),
             world_data: SimpleBuffer::new(device, "", COPY_DST | UNIFORM),
             nodes: NodesBuffer::new(device, "", COPY_DST | STORAGE, max_nodes),
-            voxel_materials: SimpleBuffer::new(device, "", COPY_DST | STORAGE),
+            voxel_materials: MaterialsBuffer::new(device, "", COPY_DST | STORAGE),
             frame_count: SimpleBuffer::new(device, "", COPY_DST | UNIFORM),
         }
     }
@@ -6,7 +6,9 @@ use voxel_math::BitField;
 
 use wgpu::{Buffer, BufferDescriptor, BufferUsages, Device, Queue};
 
+use std::ops::Range;
 use std::slice;
+use std::sync::{Mutex, MutexGuard, PoisonError};
 
 //= NODE ===========================================================================================
 
@@ -29,9 +31,10 @@ use std::slice;
 /// Invalid state.
 ///
 /// ```
-/// 01______________________xxxxxxxx
+/// 01______________xxxxxxxxxxxxxxxx
 /// ```
-/// Node is a single voxel where x = voxel type.
+/// Node is a single voxel where x = voxel type, see `Voxel::BITS`.
+/// Worlds saved before the voxel type was widened only use the lowest 8 bits.
 ///
 /// ```
 /// 11xxxxxxxxxxxxxxxxxxxxxxxxxxxxxx
@@ -47,6 +50,15 @@ pub struct Node(BitField);
 impl Node {
     pub const ZERO: Self = Self(BitField::ZERO);
 
+    #[must_use]
+    pub const fn from_raw(raw: u32) -> Self {
+        Self(BitField::from_raw(raw))
+    }
+    #[must_use]
+    pub const fn raw(self) -> u32 {
+        self.0.raw()
+    }
+
     #[must_use]
     pub const fn new_leaf(voxel: Voxel) -> Self {
         let mut rs = Self::ZERO;
@@ -82,10 +94,10 @@ impl Node {
 
     #[must_use]
     pub const fn get_voxel(self) -> Voxel {
-        Voxel(self.0.get(8, 0) as u8)
+        Voxel(self.0.get(Voxel::BITS, 0) as u16)
     }
     pub const fn set_voxel(&mut self, voxel: Voxel) {
-        self.0.set(voxel.0 as u32, 8, 0);
+        self.0.set(voxel.0 as u32, Voxel::BITS, 0);
     }
 
     pub fn set_first_child(&mut self, first_child: u32) {
@@ -109,7 +121,8 @@ impl Node {
         self.set_first_child(first_child);
     }
 
-    /// Call if `Self::can_simplify` returns `true`.
+    /// Call when all the 8 children are leaves of the same `result` voxel,
+    /// the children themselves must be freed by the caller.
     pub const fn simplify(&mut self, result: Voxel) {
         self.set_split_flag(false);
         self.set_voxel(result);
@@ -135,9 +148,15 @@ impl NodesBuffer {
         Self { buf, count }
     }
 
-    pub fn write(&self, queue: &Queue, offset: u64, nodes: &[Node]) {
-        let nodes_cut = (nodes.len() as u64).min(self.count - offset);
-        let nodes: &[Node] = &nodes[0..nodes_cut as usize];
+    /// Fails, without writing anything, if the nodes don't fit in the buffer.
+    pub fn write(&self, queue: &Queue, offset: u64, nodes: &[Node]) -> Result<(), String> {
+        let end = offset + nodes.len() as u64;
+        if end > self.count {
+            return Err(format!(
+                "writing nodes {offset}..{end} overflows the buffer of {} nodes",
+                self.count
+            ));
+        }
 
         let ptr = nodes.as_ptr().cast::<u8>();
         let size = std::mem::size_of_val(nodes);
@@ -145,5 +164,146 @@ impl NodesBuffer {
         let slice = unsafe { slice::from_raw_parts(ptr, size) };
         let offset = offset * size_of::<Node>() as u64;
         queue.write_buffer(&self.buf, offset, slice);
+        Ok(())
+    }
+}
+
+//= DIRTY RANGES ===================================================================================
+
+/// Above this count the marked ranges are merged right away, so that a lot of small edits
+/// between two uploads don't keep growing the list.
+const MIN_MERGE_LEN: usize = 4096;
+
+/// The ranges of nodes modified since they were last uploaded to the GPU.
+///
+/// The ranges are taken through a shared reference, so the renderer can upload and clear them
+/// without borrowing the owner mutably.
+#[derive(Debug, Default)]
+pub struct DirtyRanges {
+    ranges: Mutex<Vec<Range<u32>>>,
+    /// Length of the list that triggers the next merge, twice the length left by the last one.
+    merge_len: usize,
+}
+
+impl DirtyRanges {
+    /// Marks the `count` nodes starting from `idx` as modified.
+    pub fn mark(&mut self, idx: u32, count: u32) {
+        if count == 0 {
+            return;
+        }
+        let ranges = self
+            .ranges
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        match ranges.last_mut() {
+            Some(last) if last.end == idx => last.end += count,
+            _ => ranges.push(idx..idx + count),
+        }
+        if ranges.len() > self.merge_len.max(MIN_MERGE_LEN) {
+            *ranges = merge_ranges(std::mem::take(ranges));
+            self.merge_len = ranges.len() * 2;
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Returns the modified ranges sorted and merged, so none of them overlap or touch,
+    /// and marks every node as clean.
+    #[must_use]
+    pub fn take(&self) -> Vec<Range<u32>> {
+        merge_ranges(std::mem::take(&mut *self.lock()))
+    }
+
+    /// Marks again ranges returned by `DirtyRanges::take`, that couldn't be uploaded.
+    pub fn put_back(&self, ranges: impl IntoIterator<Item = Range<u32>>) {
+        self.lock().extend(ranges);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Range<u32>>> {
+        self.ranges.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Clone for DirtyRanges {
+    fn clone(&self) -> Self {
+        Self {
+            ranges: Mutex::new(self.lock().clone()),
+            merge_len: self.merge_len,
+        }
+    }
+}
+
+/// Sorts the ranges and merges the overlapping and adjacent ones.
+#[must_use]
+pub fn merge_ranges(mut ranges: Vec<Range<u32>>) -> Vec<Range<u32>> {
+    ranges.sort_unstable_by_key(|range| range.start);
+
+    let mut result: Vec<Range<u32>> = Vec::with_capacity(ranges.len());
+    for range in ranges.into_iter().filter(|range| !range.is_empty()) {
+        match result.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => result.push(range),
+        }
+    }
+    result
+}
+
+/// A pool of nodes that keeps track of the ones modified, see `Renderer::sync_world`.
+pub trait DirtyNodes {
+    fn nodes(&self) -> &[Node];
+    fn dirty_ranges(&self) -> &DirtyRanges;
+}
+
+//= TESTS ==========================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn covered(ranges: &[Range<u32>]) -> Vec<u32> {
+        let mut nodes: Vec<u32> = ranges.iter().flat_map(Clone::clone).collect();
+        nodes.sort_unstable();
+        nodes.dedup();
+        nodes
+    }
+
+    #[test]
+    fn merged_ranges_cover_exactly_the_marked_nodes() {
+        // Xorshift, good enough to scatter the ranges.
+        let mut state = 0x2545_f491_u32;
+        let mut rand = |max: u32| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state % max
+        };
+        for _ in 0..100 {
+            let mut dirty = DirtyRanges::default();
+            let mut marked = vec![];
+            for _ in 0..rand(50) {
+                let (idx, count) = (rand(500), rand(20));
+                dirty.mark(idx, count);
+                marked.push(idx..idx + count);
+            }
+
+            let merged = dirty.take();
+            assert_eq!(covered(&merged), covered(&marked));
+            // Sorted, not empty and separated by at least one clean node.
+            assert!(merged.iter().all(|range| !range.is_empty()));
+            assert!(merged.windows(2).all(|pair| pair[0].end < pair[1].start));
+            assert!(dirty.is_empty());
+
+            dirty.put_back(merged.clone());
+            assert_eq!(dirty.take(), merged);
+        }
+    }
+
+    #[test]
+    fn adjacent_and_overlapping_ranges_are_merged() {
+        let merged = merge_ranges(vec![9..17, 0..1, 1..9, 20..24, 22..23, 30..30]);
+        assert_eq!(merged, vec![0..17, 20..24]);
     }
 }
@@ -6,9 +6,22 @@ pub struct Settings {
     pub max_ray_bounces: u32,
     pub samples_per_pixel: u32,
     pub sun_intensity: f32,
-    pub _padding0: u32,
+    /// In stops, the image is multiplied by `2^exposure` before the tonemapping.
+    pub exposure: f32,
     pub sky_color: [f32; 3],
-    pub _padding1: u32,
+    pub _padding0: u32,
     pub sun_pos: [f32; 3],
-    pub _padding2: u32,
+    pub tonemapper: Tonemapper,
+}
+
+//= TONEMAPPER =====================================================================================
+
+/// How the HDR image is mapped to the displayable range.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum Tonemapper {
+    Reinhard = 0,
+    #[default]
+    Aces = 1,
+    AgX = 2,
 }
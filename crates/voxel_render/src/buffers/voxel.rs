@@ -1,3 +1,9 @@
+//= IMPORTS ========================================================================================
+
+use wgpu::{Buffer, BufferDescriptor, BufferUsages, Device, Queue};
+
+use std::slice;
+
 //= VOXEL NAMES ====================================================================================
 
 pub static VOXEL_NAMES: &[&str] = &[
@@ -24,6 +30,10 @@ pub static VOXEL_NAMES: &[&str] = &[
     "Gold",
     "Mirror",
     "Bright",
+    "Coal",
+    "Iron",
+    "Copper",
+    "Crystal",
 ];
 
 //= VOXEL MATERIALS ================================================================================
@@ -53,15 +63,25 @@ pub static VOXEL_MATERIALS: &[Material] = &[
     Material::new_solid([0.83,  0.68,  0.22 ], 0.3),                   // Gold
     Material::new_solid([1.0,   1.0,   1.0  ], 0.0),                   // Mirror
     Material::new_solid([1.0,   1.0,   1.0  ], 1.0).emit(5.0), // Bright
+    Material::new_solid([0.08,  0.08,  0.08 ], 1.0),                   // Coal
+    Material::new_solid([0.62,  0.50,  0.44 ], 0.7),                   // Iron
+    Material::new_solid([0.72,  0.45,  0.20 ], 0.5),                   // Copper
+    Material::new_solid([0.55,  0.30,  0.90 ], 0.1).emit(0.5), // Crystal
 ];
 
 //= VOXEL ==========================================================================================
 
+/// How many voxel types the leaf nodes can address, and the materials buffer can hold.
+pub const MAX_VOXEL_TYPES: usize = 1 << Voxel::BITS;
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 #[repr(transparent)]
-pub struct Voxel(pub u8);
+pub struct Voxel(pub u16);
 
 impl Voxel {
+    /// Bits of a leaf node storing its voxel.
+    pub const BITS: u32 = 16;
+
     pub const AIR: Self = Self(0);
     pub const STONE: Self = Self(1);
     pub const DIRT: Self = Self(2);
@@ -85,12 +105,25 @@ impl Voxel {
     pub const GOLD: Self = Self(20);
     pub const MIRROR: Self = Self(21);
     pub const BRIGHT: Self = Self(22);
+    pub const COAL: Self = Self(23);
+    pub const IRON: Self = Self(24);
+    pub const COPPER: Self = Self(25);
+    pub const CRYSTAL: Self = Self(26);
 
     #[must_use]
     pub fn display_name(&self) -> &'static str {
         VOXEL_NAMES[self.0 as usize]
     }
 
+    /// The voxel with the given display name, if any.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        VOXEL_NAMES
+            .iter()
+            .position(|n| *n == name)
+            .map(|idx| Self(idx as u16))
+    }
+
     #[must_use]
     pub fn is_empty(self) -> bool {
         self == Self::AIR || self == Self::WATER
@@ -170,3 +203,45 @@ impl Material {
         self
     }
 }
+
+//= MATERIALS BUFFER ===============================================================================
+
+/// The material of every voxel type, indexed by the voxel id.
+pub struct MaterialsBuffer {
+    pub buf: Buffer,
+    pub count: u64,
+}
+
+impl MaterialsBuffer {
+    /// Allocates the materials of all the `MAX_VOXEL_TYPES` voxel types.
+    #[must_use]
+    pub fn new(device: &Device, label: &str, usage: BufferUsages) -> Self {
+        let count = MAX_VOXEL_TYPES as u64;
+        let buf = device.create_buffer(&BufferDescriptor {
+            label: Some(label),
+            size: count * size_of::<Material>() as u64,
+            usage,
+            mapped_at_creation: false,
+        });
+        Self { buf, count }
+    }
+
+    /// Fails, without writing anything, if the materials don't fit in the buffer.
+    pub fn write(&self, queue: &Queue, offset: u64, materials: &[Material]) -> Result<(), String> {
+        let end = offset + materials.len() as u64;
+        if end > self.count {
+            return Err(format!(
+                "writing materials {offset}..{end} overflows the buffer of {} voxel types",
+                self.count
+            ));
+        }
+
+        let ptr = materials.as_ptr().cast::<u8>();
+        let size = size_of_val(materials);
+        #[allow(unsafe_code)]
+        let slice = unsafe { slice::from_raw_parts(ptr, size) };
+        let offset = offset * size_of::<Material>() as u64;
+        queue.write_buffer(&self.buf, offset, slice);
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+//= IMPORTS ========================================================================================
+
+use glam::U16Vec2;
+use png::{BitDepth, ColorType, Encoder, SrgbRenderingIntent};
+use wgpu::TextureFormat;
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+//= CAPTURE TARGET =================================================================================
+
+/// The textures of the renderer that can be saved with `Renderer::capture`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CaptureTarget {
+    /// The image shown on screen, crosshair included.
+    Frame,
+    /// The noisy HDR output of the path tracer, clamped to the displayable range.
+    Result,
+    /// The HDR output of the denoiser, clamped to the displayable range.
+    Denoised,
+    /// The output of the tonemapper, the frame without the crosshair.
+    Tonemapped,
+    /// The normals found by the ray tracer, stored as they are.
+    Normal,
+}
+
+//= CONVERSION =====================================================================================
+
+/// Converts the texels read back from a texture of `format` into opaque RGBA8 pixels,
+/// sRGB encoded unless `raw`.
+pub(crate) fn to_rgba8(
+    format: TextureFormat,
+    raw: bool,
+    mut texels: Vec<u8>,
+) -> Result<Vec<u8>, String> {
+    let encode = |c: f32| {
+        let c = c.clamp(0.0, 1.0);
+        if raw {
+            (c * 255.0).round() as u8
+        } else {
+            linear_to_srgb(c)
+        }
+    };
+    match format {
+        TextureFormat::Rgba32Float => {
+            texels = texels
+                .chunks_exact(4)
+                .map(|c| encode(f32::from_le_bytes([c[0], c[1], c[2], c[3]])))
+                .collect();
+        }
+        TextureFormat::Rgba16Float => {
+            texels = texels
+                .chunks_exact(2)
+                .map(|c| encode(f16_to_f32(u16::from_le_bytes([c[0], c[1]]))))
+                .collect();
+        }
+        TextureFormat::Rgba8UnormSrgb => (),
+        TextureFormat::Bgra8UnormSrgb => texels.chunks_exact_mut(4).for_each(|p| p.swap(0, 2)),
+        TextureFormat::Rgba8Unorm if raw => (),
+        TextureFormat::Rgba8Unorm => texels.chunks_exact_mut(4).for_each(|p| {
+            p[..3]
+                .iter_mut()
+                .for_each(|c| *c = linear_to_srgb(f32::from(*c) / 255.0));
+        }),
+        TextureFormat::Bgra8Unorm => texels.chunks_exact_mut(4).for_each(|p| p.swap(0, 2)),
+        _ => return Err(format!("Can't capture a texture in the {format:?} format")),
+    }
+    for pixel in texels.chunks_exact_mut(4) {
+        pixel[3] = u8::MAX;
+    }
+    Ok(texels)
+}
+
+fn linear_to_srgb(c: f32) -> u8 {
+    let srgb = if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055f32.mul_add(c.powf(1.0 / 2.4), -0.055)
+    };
+    (srgb * 255.0).round() as u8
+}
+
+/// Decodes an IEEE half precision float.
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits >> 15 == 0 { 1.0 } else { -1.0 };
+    let exponent = i32::from(bits >> 10 & 0x1F);
+    let mantissa = f32::from(bits & 0x3FF);
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1F if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1F => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+//= PNG ============================================================================================
+
+/// Writes RGBA8 pixels, rows from top to bottom, as a PNG file at `path`.
+pub(crate) fn write_png(path: &Path, size: U16Vec2, pixels: &[u8]) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("Can't create {}: {e}", path.display()))?;
+    let mut encoder = Encoder::new(BufWriter::new(file), u32::from(size.x), u32::from(size.y));
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(BitDepth::Eight);
+    encoder.set_source_srgb(SrgbRenderingIntent::Perceptual);
+
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(pixels).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())
+}
+
+//= TESTS ==========================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_decodes(bits: u16, value: f32) {
+        assert_eq!(f16_to_f32(bits).to_bits(), value.to_bits(), "{bits:#06x}");
+    }
+
+    #[test]
+    fn half_floats_are_decoded() {
+        assert_decodes(0x3C00, 1.0);
+        assert_decodes(0xC000, -2.0);
+        assert_decodes(0x3555, 0.333_251_95);
+        assert_decodes(0x0000, 0.0);
+        // Subnormals, the smallest and the largest.
+        assert_decodes(0x0001, 2f32.powi(-24));
+        assert_decodes(0x83FF, -1023.0 * 2f32.powi(-24));
+        assert_decodes(0x7BFF, 65504.0);
+        assert_decodes(0x7C00, f32::INFINITY);
+        assert_decodes(0xFC00, f32::NEG_INFINITY);
+        assert!(f16_to_f32(0x7E00).is_nan());
+    }
+
+    #[test]
+    fn float_texels_are_clamped_and_encoded() {
+        let texels = [0.5f32, 0.0, 2.0, 0.25, -1.0, 1.0, 0.002, 0.0]
+            .iter()
+            .flat_map(|c| c.to_le_bytes())
+            .collect::<Vec<_>>();
+        let srgb = to_rgba8(TextureFormat::Rgba32Float, false, texels.clone()).unwrap();
+        assert_eq!(srgb, [188, 0, 255, 255, 0, 255, 7, 255]);
+        let raw = to_rgba8(TextureFormat::Rgba32Float, true, texels).unwrap();
+        assert_eq!(raw, [128, 0, 255, 255, 0, 255, 1, 255]);
+
+        // The same values as half floats, 0.002 rounded to the nearest one.
+        let halves = [
+            0x3800u16, 0x0000, 0x4000, 0x3400, 0xBC00, 0x3C00, 0x1819, 0x0000,
+        ]
+        .iter()
+        .flat_map(|c| c.to_le_bytes())
+        .collect::<Vec<_>>();
+        let srgb = to_rgba8(TextureFormat::Rgba16Float, false, halves.clone()).unwrap();
+        assert_eq!(srgb, [188, 0, 255, 255, 0, 255, 7, 255]);
+        let raw = to_rgba8(TextureFormat::Rgba16Float, true, halves).unwrap();
+        assert_eq!(raw, [128, 0, 255, 255, 0, 255, 1, 255]);
+    }
+
+    #[test]
+    fn unorm_texels_are_encoded() {
+        let texels = vec![128, 0, 255, 40, 1, 64, 200, 0];
+        let srgb = to_rgba8(TextureFormat::Rgba8Unorm, false, texels.clone()).unwrap();
+        assert_eq!(srgb, [188, 0, 255, 255, 13, 137, 229, 255]);
+        let raw = to_rgba8(TextureFormat::Rgba8Unorm, true, texels.clone()).unwrap();
+        assert_eq!(raw, [128, 0, 255, 255, 1, 64, 200, 255]);
+        // Already sRGB encoded, only the alpha changes.
+        let pixels = to_rgba8(TextureFormat::Rgba8UnormSrgb, false, texels).unwrap();
+        assert_eq!(pixels, [128, 0, 255, 255, 1, 64, 200, 255]);
+    }
+
+    #[test]
+    fn bgra_texels_are_swizzled() {
+        let texels = vec![10, 20, 30, 40, 50, 60, 70, 0];
+        for format in [TextureFormat::Bgra8Unorm, TextureFormat::Bgra8UnormSrgb] {
+            let pixels = to_rgba8(format, false, texels.clone()).unwrap();
+            assert_eq!(pixels, [30, 20, 10, 255, 70, 60, 50, 255]);
+        }
+        assert!(to_rgba8(TextureFormat::R32Float, false, vec![0; 4]).is_err());
+    }
+}
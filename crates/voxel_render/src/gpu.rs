@@ -31,6 +31,31 @@ pub fn request_adapter(instance: &Instance, surface: &Surface<'static>) -> Resul
     Ok(adapter)
 }
 
+/// Picks an adapter that doesn't need to present to a surface, the fallback (software) one
+/// if there is any, so the images don't depend on the GPU, otherwise any available one.
+pub fn request_headless_adapter(instance: &Instance) -> Result<Adapter, String> {
+    log_possible_adapters(supported_backends(), instance);
+
+    let request = |force_fallback_adapter| {
+        async {
+            instance
+                .request_adapter(&RequestAdapterOptions {
+                    power_preference: PowerPreference::HighPerformance,
+                    compatible_surface: None,
+                    force_fallback_adapter,
+                })
+                .await
+        }
+        .block_on()
+    };
+
+    let adapter = request(true)
+        .or_else(|_| request(false))
+        .map_err(|e| format!("{e:?}({e})."))?;
+    log_picked_adapter(&adapter);
+    Ok(adapter)
+}
+
 /// Log all the adapters' info.
 fn log_possible_adapters(backends: wgpu::Backends, wgpu_instance: &Instance) {
     for (i, adapter) in wgpu_instance
@@ -3,6 +3,7 @@
 extern crate core;
 
 mod buffers;
+mod capture;
 mod gpu;
 mod passes;
 mod renderer;
@@ -11,6 +12,8 @@ mod texture;
 //= RE-EXPORTS =====================================================================================
 
 pub use buffers::*;
+pub use capture::CaptureTarget;
+pub use passes::{DEFAULT_DENOISER_ITERATIONS, MAX_DENOISER_ITERATIONS};
 pub use renderer::*;
 
 //= BACKENDS =======================================================================================
@@ -3,92 +3,180 @@
 use glam::U16Vec2;
 use wgpu::{
     BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
-    BindGroupLayoutEntry, BindingResource, BindingType, CommandEncoder, ComputePassDescriptor,
-    ComputePipeline, ComputePipelineDescriptor, Device, PipelineCompilationOptions,
-    PipelineLayoutDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages,
-    StorageTextureAccess, TextureSampleType, TextureViewDimension,
+    BindGroupLayoutEntry, BindingResource, BindingType, BufferUsages, CommandEncoder,
+    ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor, Device,
+    PipelineCompilationOptions, PipelineLayout, PipelineLayoutDescriptor, Queue,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, StorageTextureAccess, TextureSampleType,
+    TextureViewDimension,
 };
 
 use crate::bind_group_layout_entries;
-use crate::passes::DENOISER_SRC;
-use crate::texture::{RESULT_TEX_FORMAT, TextureHandler};
+use crate::buffers::SimpleBuffer;
+use crate::passes::{DENOISER_SRC, uniform_binding_type};
+use crate::texture::{HDR_TEX_FORMAT, TextureHandler};
+
+//= CONSTANTS ======================================================================================
+
+/// Each à-trous iteration doubles the step of the previous, up to 16 pixels apart.
+pub const MAX_DENOISER_ITERATIONS: u32 = 5;
+pub const DEFAULT_DENOISER_ITERATIONS: u32 = 4;
 
 //= DENOISER (COMPUTE) SHADER ======================================================================
 
+/// The textures read and written by the denoiser.
+pub(crate) struct DenoiserTextures<'a> {
+    /// The accumulated colors, the input.
+    pub result: &'a TextureHandler,
+    pub normal: &'a TextureHandler,
+    pub position: &'a TextureHandler,
+    /// The samples accumulated by each pixel.
+    pub history: &'a TextureHandler,
+    /// Where the last iteration writes, the output.
+    pub denoised: &'a TextureHandler,
+    /// Swapped with `denoised` between the iterations.
+    pub swap: &'a TextureHandler,
+}
+
+/// An edge-aware à-trous wavelet filter, guided by the variance of the accumulated samples
+/// and by the G-buffer of the ray tracer.
 pub(crate) struct DenoiserPass {
-    pub pipeline: ComputePipeline,
+    pub variance_pipeline: ComputePipeline,
+    pub atrous_pipeline: ComputePipeline,
     pub bind_group_layout: BindGroupLayout,
-    pub bind_group: BindGroup,
+    pub variance_bind_group: BindGroup,
+    /// One for each iteration, with the step in `step_buffers`.
+    pub atrous_bind_groups: Vec<BindGroup>,
+    pub step_buffers: Vec<SimpleBuffer<u32>>,
+    pub iterations: u32,
 }
 
 impl DenoiserPass {
-    pub(crate) fn new(device: &Device, res_tex: &TextureHandler, den_tex: &TextureHandler) -> Self {
+    pub(crate) fn new(device: &Device, queue: &Queue, textures: &DenoiserTextures) -> Self {
         let shader_module = device.create_shader_module(ShaderModuleDescriptor {
             label: Some("#denoiser.shader-module"),
             source: ShaderSource::Wgsl(DENOISER_SRC.into()),
         });
+        let texture_binding_type = BindingType::Texture {
+            sample_type: TextureSampleType::Float { filterable: false },
+            view_dimension: TextureViewDimension::D2,
+            multisampled: false,
+        };
         let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: Some("#denoiser.bind-group-layout"),
             entries: &bind_group_layout_entries!(
-                0 => (COMPUTE) BindingType::Texture {
-                    sample_type: TextureSampleType::default(),
-                    view_dimension: TextureViewDimension::D2,
-                    multisampled: false,
-                },
+                0 => (COMPUTE) texture_binding_type,
                 1 => (COMPUTE) BindingType::StorageTexture {
                     access: StorageTextureAccess::WriteOnly,
-                    format: RESULT_TEX_FORMAT,
+                    format: HDR_TEX_FORMAT,
                     view_dimension: TextureViewDimension::D2,
-                }
+                },
+                2 => (COMPUTE) texture_binding_type,
+                3 => (COMPUTE) texture_binding_type,
+                4 => (COMPUTE) texture_binding_type,
+                5 => (COMPUTE) uniform_binding_type(),
             ),
         });
-        let bind_group = Self::create_bind_group(device, &bind_group_layout, res_tex, den_tex);
 
         let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Some("#denoiser.pipeline-layout"),
             bind_group_layouts: &[Some(&bind_group_layout)],
             immediate_size: 0,
         });
-        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
-            label: Some("#denoiser.pipeline"),
-            layout: Some(&pipeline_layout),
-            module: &shader_module,
-            entry_point: Some("update"),
-            compilation_options: PipelineCompilationOptions::default(),
-            cache: None,
-        });
+        let create_pipeline = |layout: &PipelineLayout, entry_point| {
+            device.create_compute_pipeline(&ComputePipelineDescriptor {
+                label: Some("#denoiser.pipeline"),
+                layout: Some(layout),
+                module: &shader_module,
+                entry_point: Some(entry_point),
+                compilation_options: PipelineCompilationOptions::default(),
+                cache: None,
+            })
+        };
+        let variance_pipeline = create_pipeline(&pipeline_layout, "variance");
+        let atrous_pipeline = create_pipeline(&pipeline_layout, "atrous");
+
+        let step_buffers = (0..MAX_DENOISER_ITERATIONS)
+            .map(|i| {
+                let buffer = SimpleBuffer::new(
+                    device,
+                    "#denoiser.step-buffer",
+                    BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+                );
+                buffer.write(queue, &(1 << i));
+                buffer
+            })
+            .collect::<Vec<_>>();
+
+        let iterations = DEFAULT_DENOISER_ITERATIONS;
+        let (variance_bind_group, atrous_bind_groups) = Self::create_bind_groups(
+            device,
+            &bind_group_layout,
+            &step_buffers,
+            iterations,
+            textures,
+        );
 
         Self {
-            pipeline,
+            variance_pipeline,
+            atrous_pipeline,
             bind_group_layout,
-            bind_group,
+            variance_bind_group,
+            atrous_bind_groups,
+            step_buffers,
+            iterations,
         }
     }
 
-    pub(crate) fn create_bind_group(
+    /// The iterations write alternately to `denoised` and `swap`, so that the last one
+    /// always ends in `denoised`.
+    pub(crate) fn create_bind_groups(
         device: &Device,
         layout: &BindGroupLayout,
-        res_tex: &TextureHandler,
-        den_tex: &TextureHandler,
-    ) -> BindGroup {
-        device.create_bind_group(&BindGroupDescriptor {
-            label: Some("#denoiser.bind-group"),
-            layout,
-            entries: &crate::bind_group_entries!(
-                0 => BindingResource::TextureView(&res_tex.view),
-                1 => BindingResource::TextureView(&den_tex.view),
-            ),
-        })
+        step_buffers: &[SimpleBuffer<u32>],
+        iterations: u32,
+        textures: &DenoiserTextures,
+    ) -> (BindGroup, Vec<BindGroup>) {
+        let create_bind_group = |input: &TextureHandler, output: &TextureHandler, step: usize| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("#denoiser.bind-group"),
+                layout,
+                entries: &crate::bind_group_entries!(
+                    0 => BindingResource::TextureView(&input.view),
+                    1 => BindingResource::TextureView(&output.view),
+                    2 => BindingResource::TextureView(&textures.normal.view),
+                    3 => BindingResource::TextureView(&textures.position.view),
+                    4 => BindingResource::TextureView(&textures.history.view),
+                    5 => step_buffers[step].0.as_entire_binding(),
+                ),
+            })
+        };
+        // The output of the pass running `passes_left` passes before the last one.
+        let output = |passes_left: u32| {
+            if passes_left.is_multiple_of(2) {
+                textures.denoised
+            } else {
+                textures.swap
+            }
+        };
+
+        let variance_bind_group = create_bind_group(textures.result, output(iterations), 0);
+        let atrous_bind_groups = (0..iterations)
+            .map(|i| {
+                let passes_left = iterations - 1 - i;
+                create_bind_group(output(passes_left + 1), output(passes_left), i as usize)
+            })
+            .collect();
+        (variance_bind_group, atrous_bind_groups)
     }
 
-    pub(crate) fn recreate_bind_group(
-        &mut self,
-        device: &Device,
-        res_tex: &TextureHandler,
-        den_tex: &TextureHandler,
-    ) {
-        self.bind_group =
-            Self::create_bind_group(device, &self.bind_group_layout, res_tex, den_tex);
+    pub(crate) fn recreate_bind_groups(&mut self, device: &Device, textures: &DenoiserTextures) {
+        (self.variance_bind_group, self.atrous_bind_groups) = Self::create_bind_groups(
+            device,
+            &self.bind_group_layout,
+            &self.step_buffers,
+            self.iterations,
+            textures,
+        );
     }
 
     pub fn encode_pass(&self, encoder: &mut CommandEncoder, workgroups: U16Vec2) {
@@ -96,8 +184,14 @@ impl DenoiserPass {
             label: Some("#denoiser-pass"),
             timestamp_writes: None,
         });
-        pass.set_pipeline(&self.pipeline);
-        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_pipeline(&self.variance_pipeline);
+        pass.set_bind_group(0, &self.variance_bind_group, &[]);
         pass.dispatch_workgroups(u32::from(workgroups.x), u32::from(workgroups.y), 1);
+
+        pass.set_pipeline(&self.atrous_pipeline);
+        for bind_group in &self.atrous_bind_groups {
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.dispatch_workgroups(u32::from(workgroups.x), u32::from(workgroups.y), 1);
+        }
     }
 }
@@ -4,13 +4,18 @@ mod denoiser;
 mod path_tracer;
 mod ray_tracer;
 mod screen;
+mod temporal;
+mod tonemap;
 
 //= RE-EXPORTS =====================================================================================
 
 pub(crate) use denoiser::*;
+pub use denoiser::{DEFAULT_DENOISER_ITERATIONS, MAX_DENOISER_ITERATIONS};
 pub(crate) use path_tracer::*;
 pub(crate) use ray_tracer::*;
 pub(crate) use screen::*;
+pub(crate) use temporal::*;
+pub(crate) use tonemap::*;
 
 //= IMPORTS ========================================================================================
 
@@ -19,8 +24,10 @@ use wgpu::{BindingType, BufferBindingType};
 //= CONSTANTS ======================================================================================
 
 static RAY_TRACER_SRC: &str = include_str!("../shaders/ray_tracer.wgsl");
+static TEMPORAL_SRC: &str = include_str!("../shaders/temporal.wgsl");
 static PATH_TRACER_SRC: &str = include_str!("../shaders/path_tracer.wgsl");
 static DENOISER_SRC: &str = include_str!("../shaders/denoiser.wgsl");
+static TONEMAP_SRC: &str = include_str!("../shaders/tonemap.wgsl");
 static SCREEN_SHADER_SRC: &str = include_str!("../shaders/screen.wgsl");
 
 //= BINDING TYPES ==================================================================================
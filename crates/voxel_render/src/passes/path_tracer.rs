@@ -26,6 +26,7 @@ impl PathTracerPass {
         device: &Device,
         res_tex: &TextureHandler,
         prev_tex: &TextureHandler,
+        history_tex: &TextureHandler,
         buffers: &Buffers,
     ) -> Self {
         let shader_module = device.create_shader_module(ShaderModuleDescriptor {
@@ -47,14 +48,25 @@ impl PathTracerPass {
                 5 => (COMPUTE) uniform_binding_type(),
                 6 => (COMPUTE) uniform_binding_type(),
                 7 => (COMPUTE) BindingType::Texture {
-                    sample_type: TextureSampleType::default(),
+                    sample_type: TextureSampleType::Float { filterable: false },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                8 => (COMPUTE) BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: false },
                     view_dimension: TextureViewDimension::D2,
                     multisampled: false,
                 }
             ),
         });
-        let bind_group =
-            Self::create_bind_group(device, &bind_group_layout, res_tex, prev_tex, buffers);
+        let bind_group = Self::create_bind_group(
+            device,
+            &bind_group_layout,
+            res_tex,
+            prev_tex,
+            history_tex,
+            buffers,
+        );
 
         let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Some("#pathtracer.pipeline-layout"),
@@ -82,6 +94,7 @@ impl PathTracerPass {
         layout: &BindGroupLayout,
         res_tex: &TextureHandler,
         prev_tex: &TextureHandler,
+        history_tex: &TextureHandler,
         buffers: &Buffers,
     ) -> BindGroup {
         device.create_bind_group(&BindGroupDescriptor {
@@ -92,10 +105,11 @@ impl PathTracerPass {
                 1 => buffers.camera_buffer.0.as_entire_binding(),
                 2 => buffers.settings.0.as_entire_binding(),
                 3 => buffers.nodes.buf.as_entire_binding(),
-                4 => buffers.voxel_materials.0.as_entire_binding(),
+                4 => buffers.voxel_materials.buf.as_entire_binding(),
                 5 => buffers.frame_count.0.as_entire_binding(),
                 6 => buffers.world_data.0.as_entire_binding(),
                 7 => BindingResource::TextureView(&prev_tex.view),
+                8 => BindingResource::TextureView(&history_tex.view),
             ),
         })
     }
@@ -105,10 +119,17 @@ impl PathTracerPass {
         device: &Device,
         res_tex: &TextureHandler,
         prev_tex: &TextureHandler,
+        history_tex: &TextureHandler,
         buffers: &Buffers,
     ) {
-        self.bind_group =
-            Self::create_bind_group(device, &self.bind_group_layout, res_tex, prev_tex, buffers);
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            res_tex,
+            prev_tex,
+            history_tex,
+            buffers,
+        );
     }
 
     pub fn encode_pass(&self, encoder: &mut CommandEncoder, workgroups: U16Vec2) {
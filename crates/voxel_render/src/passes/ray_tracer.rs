@@ -11,7 +11,7 @@ use wgpu::{
 
 use crate::buffers::Buffers;
 use crate::passes::{RAY_TRACER_SRC, storage_binding_type, uniform_binding_type};
-use crate::texture::{RESULT_TEX_FORMAT, TextureHandler};
+use crate::texture::{NORMAL_TEX_FORMAT, POSITION_TEX_FORMAT, TextureHandler};
 
 //= RAY TRACER (COMPUTE) SHADER ====================================================================
 
@@ -22,7 +22,12 @@ pub(crate) struct RayTracerPass {
 }
 
 impl RayTracerPass {
-    pub(crate) fn new(device: &Device, norm_tex: &TextureHandler, buffers: &Buffers) -> Self {
+    pub(crate) fn new(
+        device: &Device,
+        norm_tex: &TextureHandler,
+        pos_tex: &TextureHandler,
+        buffers: &Buffers,
+    ) -> Self {
         let shader_module = device.create_shader_module(ShaderModuleDescriptor {
             label: Some("#raytracer.shader-module"),
             source: ShaderSource::Wgsl(RAY_TRACER_SRC.into()),
@@ -32,15 +37,21 @@ impl RayTracerPass {
             entries: &crate::bind_group_layout_entries!(
                 0 => (COMPUTE) BindingType::StorageTexture {
                     access: StorageTextureAccess::WriteOnly,
-                    format: RESULT_TEX_FORMAT,
+                    format: NORMAL_TEX_FORMAT,
                     view_dimension: TextureViewDimension::D2,
                 },
                 1 => (COMPUTE) uniform_binding_type(),
                 2 => (COMPUTE) storage_binding_type(true),
                 3 => (COMPUTE) uniform_binding_type(),
+                4 => (COMPUTE) BindingType::StorageTexture {
+                    access: StorageTextureAccess::WriteOnly,
+                    format: POSITION_TEX_FORMAT,
+                    view_dimension: TextureViewDimension::D2,
+                },
             ),
         });
-        let bind_group = Self::create_bind_group(device, &bind_group_layout, norm_tex, buffers);
+        let bind_group =
+            Self::create_bind_group(device, &bind_group_layout, norm_tex, pos_tex, buffers);
 
         let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Some("#raytracer.pipeline-layout"),
@@ -67,6 +78,7 @@ impl RayTracerPass {
         device: &Device,
         layout: &BindGroupLayout,
         norm_tex: &TextureHandler,
+        pos_tex: &TextureHandler,
         buffers: &Buffers,
     ) -> BindGroup {
         device.create_bind_group(&BindGroupDescriptor {
@@ -77,6 +89,7 @@ impl RayTracerPass {
                 1 => buffers.camera_buffer.0.as_entire_binding(),
                 2 => buffers.nodes.buf.as_entire_binding(),
                 3 => buffers.world_data.0.as_entire_binding(),
+                4 => BindingResource::TextureView(&pos_tex.view),
             ),
         })
     }
@@ -85,10 +98,11 @@ impl RayTracerPass {
         &mut self,
         device: &Device,
         norm_tex: &TextureHandler,
+        pos_tex: &TextureHandler,
         buffers: &Buffers,
     ) {
         self.bind_group =
-            Self::create_bind_group(device, &self.bind_group_layout, norm_tex, buffers);
+            Self::create_bind_group(device, &self.bind_group_layout, norm_tex, pos_tex, buffers);
     }
 
     pub fn encode_pass(&self, encoder: &mut CommandEncoder, workgroups: U16Vec2) {
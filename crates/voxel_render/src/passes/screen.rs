@@ -62,7 +62,11 @@ impl ScreenPass {
             fragment: Some(FragmentState {
                 module: &shader_module,
                 entry_point: Some("fs_main"),
-                compilation_options: PipelineCompilationOptions::default(),
+                compilation_options: PipelineCompilationOptions {
+                    // The sRGB formats encode the colors on their own.
+                    constants: &[("ENCODE_SRGB", f64::from(!surface_format.is_srgb()))],
+                    ..PipelineCompilationOptions::default()
+                },
                 targets: &[Some(ColorTargetState {
                     format: surface_format,
                     blend: None,
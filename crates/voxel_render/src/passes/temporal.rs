@@ -0,0 +1,140 @@
+//= IMPORTS ========================================================================================
+
+use glam::U16Vec2;
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, CommandEncoder, ComputePassDescriptor,
+    ComputePipeline, ComputePipelineDescriptor, Device, PipelineCompilationOptions,
+    PipelineLayoutDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    StorageTextureAccess, TextureSampleType, TextureViewDimension,
+};
+
+use crate::bind_group_layout_entries;
+use crate::buffers::Buffers;
+use crate::passes::{TEMPORAL_SRC, uniform_binding_type};
+use crate::texture::{HISTORY_TEX_FORMAT, RESULT_TEX_FORMAT, TextureHandler};
+
+//= TEMPORAL (COMPUTE) SHADER ======================================================================
+
+/// The textures read and written by the temporal reprojection.
+pub(crate) struct TemporalTextures<'a> {
+    pub position: &'a TextureHandler,
+    pub normal: &'a TextureHandler,
+    pub prev_position: &'a TextureHandler,
+    pub prev_normal: &'a TextureHandler,
+    pub prev_result: &'a TextureHandler,
+    pub prev_history: &'a TextureHandler,
+    /// The accumulated samples of the previous frame, moved to where they are now.
+    pub reprojected: &'a TextureHandler,
+    pub history: &'a TextureHandler,
+}
+
+/// Reprojects the samples accumulated in the previous frame with the previous camera,
+/// keeping only those of the same surface, so the path tracer can keep adding up to them.
+pub(crate) struct TemporalPass {
+    pub pipeline: ComputePipeline,
+    pub bind_group_layout: BindGroupLayout,
+    pub bind_group: BindGroup,
+}
+
+impl TemporalPass {
+    pub(crate) fn new(device: &Device, textures: &TemporalTextures, buffers: &Buffers) -> Self {
+        let shader_module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("#temporal.shader-module"),
+            source: ShaderSource::Wgsl(TEMPORAL_SRC.into()),
+        });
+        let texture_binding_type = BindingType::Texture {
+            sample_type: TextureSampleType::Float { filterable: false },
+            view_dimension: TextureViewDimension::D2,
+            multisampled: false,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("#temporal.bind-group-layout"),
+            entries: &bind_group_layout_entries!(
+                0 => (COMPUTE) texture_binding_type,
+                1 => (COMPUTE) texture_binding_type,
+                2 => (COMPUTE) texture_binding_type,
+                3 => (COMPUTE) texture_binding_type,
+                4 => (COMPUTE) texture_binding_type,
+                5 => (COMPUTE) texture_binding_type,
+                6 => (COMPUTE) BindingType::StorageTexture {
+                    access: StorageTextureAccess::WriteOnly,
+                    format: RESULT_TEX_FORMAT,
+                    view_dimension: TextureViewDimension::D2,
+                },
+                7 => (COMPUTE) BindingType::StorageTexture {
+                    access: StorageTextureAccess::WriteOnly,
+                    format: HISTORY_TEX_FORMAT,
+                    view_dimension: TextureViewDimension::D2,
+                },
+                8 => (COMPUTE) uniform_binding_type(),
+                9 => (COMPUTE) uniform_binding_type(),
+            ),
+        });
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, textures, buffers);
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("#temporal.pipeline-layout"),
+            bind_group_layouts: &[Some(&bind_group_layout)],
+            immediate_size: 0,
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("#temporal.pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader_module,
+            entry_point: Some("update"),
+            compilation_options: PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    pub(crate) fn create_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        textures: &TemporalTextures,
+        buffers: &Buffers,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("#temporal.bind-group"),
+            layout,
+            entries: &crate::bind_group_entries!(
+                0 => BindingResource::TextureView(&textures.position.view),
+                1 => BindingResource::TextureView(&textures.normal.view),
+                2 => BindingResource::TextureView(&textures.prev_position.view),
+                3 => BindingResource::TextureView(&textures.prev_normal.view),
+                4 => BindingResource::TextureView(&textures.prev_result.view),
+                5 => BindingResource::TextureView(&textures.prev_history.view),
+                6 => BindingResource::TextureView(&textures.reprojected.view),
+                7 => BindingResource::TextureView(&textures.history.view),
+                8 => buffers.prev_camera_buffer.0.as_entire_binding(),
+                9 => buffers.frame_count.0.as_entire_binding(),
+            ),
+        })
+    }
+
+    pub(crate) fn recreate_bind_group(
+        &mut self,
+        device: &Device,
+        textures: &TemporalTextures,
+        buffers: &Buffers,
+    ) {
+        self.bind_group =
+            Self::create_bind_group(device, &self.bind_group_layout, textures, buffers);
+    }
+
+    pub fn encode_pass(&self, encoder: &mut CommandEncoder, workgroups: U16Vec2) {
+        let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("#temporal-pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.dispatch_workgroups(u32::from(workgroups.x), u32::from(workgroups.y), 1);
+    }
+}
@@ -0,0 +1,116 @@
+//= IMPORTS ========================================================================================
+
+use glam::U16Vec2;
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, CommandEncoder, ComputePassDescriptor,
+    ComputePipeline, ComputePipelineDescriptor, Device, PipelineCompilationOptions,
+    PipelineLayoutDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    StorageTextureAccess, TextureSampleType, TextureViewDimension,
+};
+
+use crate::bind_group_layout_entries;
+use crate::buffers::Buffers;
+use crate::passes::{TONEMAP_SRC, uniform_binding_type};
+use crate::texture::{HDR_TEX_FORMAT, TextureHandler};
+
+//= TONEMAP (COMPUTE) SHADER =======================================================================
+
+/// Applies the exposure and maps the HDR image to the 0..1 range with the `Tonemapper` chosen
+/// in `Settings`, the result is still linear.
+pub(crate) struct TonemapPass {
+    pub pipeline: ComputePipeline,
+    pub bind_group_layout: BindGroupLayout,
+    pub bind_group: BindGroup,
+}
+
+impl TonemapPass {
+    pub(crate) fn new(
+        device: &Device,
+        hdr_tex: &TextureHandler,
+        out_tex: &TextureHandler,
+        buffers: &Buffers,
+    ) -> Self {
+        let shader_module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("#tonemap.shader-module"),
+            source: ShaderSource::Wgsl(TONEMAP_SRC.into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("#tonemap.bind-group-layout"),
+            entries: &bind_group_layout_entries!(
+                0 => (COMPUTE) BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: false },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                1 => (COMPUTE) BindingType::StorageTexture {
+                    access: StorageTextureAccess::WriteOnly,
+                    format: HDR_TEX_FORMAT,
+                    view_dimension: TextureViewDimension::D2,
+                },
+                2 => (COMPUTE) uniform_binding_type(),
+            ),
+        });
+        let bind_group =
+            Self::create_bind_group(device, &bind_group_layout, hdr_tex, out_tex, buffers);
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("#tonemap.pipeline-layout"),
+            bind_group_layouts: &[Some(&bind_group_layout)],
+            immediate_size: 0,
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("#tonemap.pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader_module,
+            entry_point: Some("update"),
+            compilation_options: PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    pub(crate) fn create_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        hdr_tex: &TextureHandler,
+        out_tex: &TextureHandler,
+        buffers: &Buffers,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("#tonemap.bind-group"),
+            layout,
+            entries: &crate::bind_group_entries!(
+                0 => BindingResource::TextureView(&hdr_tex.view),
+                1 => BindingResource::TextureView(&out_tex.view),
+                2 => buffers.settings.0.as_entire_binding(),
+            ),
+        })
+    }
+
+    pub(crate) fn recreate_bind_group(
+        &mut self,
+        device: &Device,
+        hdr_tex: &TextureHandler,
+        out_tex: &TextureHandler,
+        buffers: &Buffers,
+    ) {
+        self.bind_group =
+            Self::create_bind_group(device, &self.bind_group_layout, hdr_tex, out_tex, buffers);
+    }
+
+    pub fn encode_pass(&self, encoder: &mut CommandEncoder, workgroups: U16Vec2) {
+        let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("#tonemap-pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.dispatch_workgroups(u32::from(workgroups.x), u32::from(workgroups.y), 1);
+    }
+}
@@ -1,40 +1,72 @@
 //= IMPORTS ========================================================================================
 
+use crate::capture::{CaptureTarget, to_rgba8, write_png};
 use crate::gpu::{
     create_instance, create_surface, create_surface_config, request_adapter, request_device,
+    request_headless_adapter,
 };
-use crate::passes::{DenoiserPass, PathTracerPass, RayTracerPass, ScreenPass};
-use crate::texture::{RESULT_TEX_FORMAT, RESULT_TEX_USAGES, TextureHandler};
-use crate::{Buffers, Camera, FrameData, Material, Node, Settings, WorldData};
+use crate::passes::{
+    DenoiserPass, DenoiserTextures, MAX_DENOISER_ITERATIONS, PathTracerPass, RayTracerPass,
+    ScreenPass, TemporalPass, TemporalTextures, TonemapPass,
+};
+use crate::texture::{
+    HDR_TEX_FORMAT, HISTORY_TEX_FORMAT, NORMAL_TEX_FORMAT, OUTPUT_TEX_FORMAT, OUTPUT_TEX_USAGES,
+    POSITION_TEX_FORMAT, RESULT_TEX_FORMAT, RESULT_TEX_USAGES, TextureHandler,
+};
+use crate::{Buffers, Camera, DirtyNodes, FrameData, Material, Node, Settings, WorldData};
 
 use glam::U16Vec2;
 use raw_window_handle as rwh;
 use wgpu::{
-    CommandBuffer, CommandEncoder, CommandEncoderDescriptor, CurrentSurfaceTexture, Device, Limits,
-    Queue, Surface, SurfaceConfiguration, SurfaceTexture, TextureView, TextureViewDescriptor,
+    CommandBuffer, CommandEncoder, CommandEncoderDescriptor, CompositeAlphaMode,
+    CurrentSurfaceTexture, Device, Limits, PresentMode, Queue, Surface, SurfaceConfiguration,
+    SurfaceTexture, TextureFormat, TextureView, TextureViewDescriptor,
 };
 
 use core::num::NonZeroU16;
 use std::iter;
+use std::ops::Range;
+use std::path::Path;
 
 //= RENDERER =======================================================================================
 
+/// Where the renderer draws the final image.
+enum Target {
+    /// The swapchain of a window.
+    Surface(Surface<'static>),
+    /// A texture owned by the renderer, see `Renderer::new_headless`.
+    Texture(TextureHandler),
+}
+
 pub struct Renderer {
-    surface: Surface<'static>,
+    target: Target,
+    /// Size and format of the target, only given to the surface if there is one.
     surface_config: SurfaceConfiguration,
     device: Device,
     queue: Queue,
 
     normal_texture: TextureHandler,
+    prev_normal_texture: TextureHandler,
+    position_texture: TextureHandler,
+    prev_position_texture: TextureHandler,
+    reprojected_texture: TextureHandler,
+    history_texture: TextureHandler,
+    prev_history_texture: TextureHandler,
     result_texture: TextureHandler,
     prev_result_texture: TextureHandler,
     denoised_texture: TextureHandler,
+    denoiser_swap_texture: TextureHandler,
+    tonemapped_texture: TextureHandler,
     buffers: Buffers,
     frame_data: FrameData,
+    /// The camera of the last `Renderer::update`.
+    prev_camera: Camera,
 
     ray_tracer_pass: RayTracerPass,
+    temporal_pass: TemporalPass,
     path_tracer_pass: PathTracerPass,
     denoiser_pass: DenoiserPass,
+    tonemap_pass: TonemapPass,
     screen_pass: ScreenPass,
 }
 
@@ -60,60 +92,137 @@ impl Renderer {
 
         surface.configure(&device, &surface_config);
 
-        let buffers = Buffers::new(&device, max_nodes);
+        Ok(Self::with_target(
+            Target::Surface(surface),
+            surface_config,
+            device,
+            queue,
+            max_nodes,
+        ))
+    }
 
-        let normal_texture = TextureHandler::new(
-            &device,
-            U16Vec2::new(surface_width, surface_height),
-            RESULT_TEX_FORMAT,
-            RESULT_TEX_USAGES,
-        );
+    /// A renderer without a window, drawing into a texture of its own that can be read back
+    /// with `Renderer::read_output`. It runs on a software adapter if there is one.
+    pub fn new_headless(width: u16, height: u16, max_nodes: u64) -> Result<Self, String> {
+        if width == 0 || height == 0 {
+            return Err("Impossible to create a headless renderer with zero size".to_string());
+        }
+        let instance = create_instance();
+        let adapter = request_headless_adapter(&instance)?;
+        let (device, queue) = request_device(&adapter, Self::max_buffer_sizes())?;
 
-        let result_texture = TextureHandler::new(
+        let surface_config = SurfaceConfiguration {
+            usage: OUTPUT_TEX_USAGES,
+            format: OUTPUT_TEX_FORMAT,
+            width: u32::from(width),
+            height: u32::from(height),
+            desired_maximum_frame_latency: 2,
+            present_mode: PresentMode::Fifo,
+            alpha_mode: CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        };
+        let output_texture = TextureHandler::new(
             &device,
-            U16Vec2::new(surface_width, surface_height),
-            RESULT_TEX_FORMAT,
-            RESULT_TEX_USAGES,
+            U16Vec2::new(width, height),
+            OUTPUT_TEX_FORMAT,
+            OUTPUT_TEX_USAGES,
         );
 
-        let prev_result_texture = TextureHandler::new(
-            &device,
-            U16Vec2::new(surface_width, surface_height),
-            RESULT_TEX_FORMAT,
-            RESULT_TEX_USAGES,
-        );
+        Ok(Self::with_target(
+            Target::Texture(output_texture),
+            surface_config,
+            device,
+            queue,
+            max_nodes,
+        ))
+    }
+
+    fn with_target(
+        target: Target,
+        surface_config: SurfaceConfiguration,
+        device: Device,
+        queue: Queue,
+        max_nodes: u64,
+    ) -> Self {
+        let size = U16Vec2::new(surface_config.width as u16, surface_config.height as u16);
+
+        let buffers = Buffers::new(&device, max_nodes);
 
-        let denoised_texture = TextureHandler::new(
+        let new_texture = |format| TextureHandler::new(&device, size, format, RESULT_TEX_USAGES);
+        let normal_texture = new_texture(NORMAL_TEX_FORMAT);
+        let prev_normal_texture = new_texture(NORMAL_TEX_FORMAT);
+        let position_texture = new_texture(POSITION_TEX_FORMAT);
+        let prev_position_texture = new_texture(POSITION_TEX_FORMAT);
+        let reprojected_texture = new_texture(RESULT_TEX_FORMAT);
+        let history_texture = new_texture(HISTORY_TEX_FORMAT);
+        let prev_history_texture = new_texture(HISTORY_TEX_FORMAT);
+        let result_texture = new_texture(RESULT_TEX_FORMAT);
+        let prev_result_texture = new_texture(RESULT_TEX_FORMAT);
+        let denoised_texture = new_texture(HDR_TEX_FORMAT);
+        let denoiser_swap_texture = new_texture(HDR_TEX_FORMAT);
+        let tonemapped_texture = new_texture(HDR_TEX_FORMAT);
+
+        let ray_tracer = RayTracerPass::new(&device, &normal_texture, &position_texture, &buffers);
+        let temporal_textures = TemporalTextures {
+            position: &position_texture,
+            normal: &normal_texture,
+            prev_position: &prev_position_texture,
+            prev_normal: &prev_normal_texture,
+            prev_result: &prev_result_texture,
+            prev_history: &prev_history_texture,
+            reprojected: &reprojected_texture,
+            history: &history_texture,
+        };
+        let temporal = TemporalPass::new(&device, &temporal_textures, &buffers);
+        let path_tracer = PathTracerPass::new(
             &device,
-            U16Vec2::new(surface_width, surface_height),
-            RESULT_TEX_FORMAT,
-            RESULT_TEX_USAGES,
+            &result_texture,
+            &reprojected_texture,
+            &history_texture,
+            &buffers,
         );
+        let denoiser_textures = DenoiserTextures {
+            result: &result_texture,
+            normal: &normal_texture,
+            position: &position_texture,
+            history: &history_texture,
+            denoised: &denoised_texture,
+            swap: &denoiser_swap_texture,
+        };
+        let denoiser_shader = DenoiserPass::new(&device, &queue, &denoiser_textures);
+        let tonemap_shader =
+            TonemapPass::new(&device, &denoised_texture, &tonemapped_texture, &buffers);
+        let screen_shader = ScreenPass::new(&device, &tonemapped_texture, surface_config.format);
 
-        let ray_tracer = RayTracerPass::new(&device, &normal_texture, &buffers);
-        let path_tracer =
-            PathTracerPass::new(&device, &result_texture, &prev_result_texture, &buffers);
-        let denoiser_shader = DenoiserPass::new(&device, &result_texture, &denoised_texture);
-        let screen_shader = ScreenPass::new(&device, &denoised_texture, surface_config.format);
-
-        Ok(Self {
-            surface,
+        Self {
+            target,
             surface_config,
             device,
             queue,
 
             normal_texture,
+            prev_normal_texture,
+            position_texture,
+            prev_position_texture,
+            reprojected_texture,
+            history_texture,
+            prev_history_texture,
             result_texture,
             prev_result_texture,
             denoised_texture,
+            denoiser_swap_texture,
+            tonemapped_texture,
             buffers,
             frame_data: FrameData::default(),
+            prev_camera: Camera::default(),
 
             ray_tracer_pass: ray_tracer,
+            temporal_pass: temporal,
             path_tracer_pass: path_tracer,
             screen_pass: screen_shader,
             denoiser_pass: denoiser_shader,
-        })
+            tonemap_pass: tonemap_shader,
+        }
     }
 
     #[must_use]
@@ -146,14 +255,53 @@ impl Renderer {
         self.buffers.world_data.write(&self.queue, world_data);
     }
 
-    pub fn write_nodes(&self, offset: u64, nodes: &[Node]) {
-        self.buffers.nodes.write(&self.queue, offset, nodes);
+    /// Fails if the nodes go past the `max_nodes` the renderer was created with.
+    pub fn write_nodes(&self, offset: u64, nodes: &[Node]) -> Result<(), String> {
+        self.buffers.nodes.write(&self.queue, offset, nodes)
+    }
+
+    /// Uploads the nodes modified since the last sync, one write per merged range.
+    /// Returns whether any node was uploaded.
+    ///
+    /// Fails if a range doesn't fit in the nodes buffer, then nothing is uploaded
+    /// and the nodes stay modified, so the GPU never holds half of the changes.
+    pub fn sync_world(&self, world: &impl DirtyNodes) -> Result<bool, String> {
+        let nodes = world.nodes();
+        let ranges = world.dirty_ranges().take();
+        let clamp = |range: &Range<u32>| {
+            let end = (range.end as usize).min(nodes.len());
+            (range.start as usize).min(end)..end
+        };
+        let capacity = self.buffers.nodes.count;
+        if let Some(range) = ranges
+            .iter()
+            .map(clamp)
+            .find(|r| !r.is_empty() && r.end as u64 > capacity)
+        {
+            world.dirty_ranges().put_back(ranges);
+            return Err(format!(
+                "Can't upload the nodes {range:?}, the buffer only holds {capacity} nodes"
+            ));
+        }
+
+        let mut synced = false;
+        for range in ranges.iter().map(clamp).filter(|range| !range.is_empty()) {
+            self.write_nodes(range.start as u64, &nodes[range])?;
+            synced = true;
+        }
+        Ok(synced)
     }
 
-    pub fn write_voxel_materials(&self, offset: u64, voxel_materials: &[Material]) {
+    /// Writes the materials of the voxels starting from the `offset` voxel id.
+    /// Fails if the materials go past the `MAX_VOXEL_TYPES` voxel types.
+    pub fn write_voxel_materials(
+        &self,
+        offset: u64,
+        voxel_materials: &[Material],
+    ) -> Result<(), String> {
         self.buffers
             .voxel_materials
-            .write_slice(&self.queue, offset, voxel_materials);
+            .write(&self.queue, offset, voxel_materials)
     }
 
     pub(crate) fn write_frame_data(&self, frame_data: &FrameData) {
@@ -169,43 +317,111 @@ impl Renderer {
         {
             self.surface_config.width = u32::from(width);
             self.surface_config.height = u32::from(height);
-            self.surface.configure(&self.device, &self.surface_config);
 
             let new_size = U16Vec2::new(width, height);
-            self.normal_texture =
-                TextureHandler::new(&self.device, new_size, RESULT_TEX_FORMAT, RESULT_TEX_USAGES);
-            self.result_texture =
-                TextureHandler::new(&self.device, new_size, RESULT_TEX_FORMAT, RESULT_TEX_USAGES);
-            self.prev_result_texture =
-                TextureHandler::new(&self.device, new_size, RESULT_TEX_FORMAT, RESULT_TEX_USAGES);
-            self.denoised_texture =
-                TextureHandler::new(&self.device, new_size, RESULT_TEX_FORMAT, RESULT_TEX_USAGES);
+            match &mut self.target {
+                Target::Surface(surface) => surface.configure(&self.device, &self.surface_config),
+                Target::Texture(texture) => {
+                    *texture = TextureHandler::new(
+                        &self.device,
+                        new_size,
+                        OUTPUT_TEX_FORMAT,
+                        OUTPUT_TEX_USAGES,
+                    );
+                }
+            }
+            let new_texture =
+                |format| TextureHandler::new(&self.device, new_size, format, RESULT_TEX_USAGES);
+            self.normal_texture = new_texture(NORMAL_TEX_FORMAT);
+            self.prev_normal_texture = new_texture(NORMAL_TEX_FORMAT);
+            self.position_texture = new_texture(POSITION_TEX_FORMAT);
+            self.prev_position_texture = new_texture(POSITION_TEX_FORMAT);
+            self.reprojected_texture = new_texture(RESULT_TEX_FORMAT);
+            self.history_texture = new_texture(HISTORY_TEX_FORMAT);
+            self.prev_history_texture = new_texture(HISTORY_TEX_FORMAT);
+            self.result_texture = new_texture(RESULT_TEX_FORMAT);
+            self.prev_result_texture = new_texture(RESULT_TEX_FORMAT);
+            self.denoised_texture = new_texture(HDR_TEX_FORMAT);
+            self.denoiser_swap_texture = new_texture(HDR_TEX_FORMAT);
+            self.tonemapped_texture = new_texture(HDR_TEX_FORMAT);
 
             self.ray_tracer_pass.recreate_bind_group(
                 &self.device,
                 &self.normal_texture,
+                &self.position_texture,
                 &self.buffers,
             );
+            self.recreate_temporal_bind_group();
             self.path_tracer_pass.recreate_bind_group(
                 &self.device,
                 &self.result_texture,
-                &self.prev_result_texture,
+                &self.reprojected_texture,
+                &self.history_texture,
                 &self.buffers,
             );
-            self.denoiser_pass.recreate_bind_group(
+            self.recreate_denoiser_bind_groups();
+            self.tonemap_pass.recreate_bind_group(
                 &self.device,
-                &self.result_texture,
                 &self.denoised_texture,
+                &self.tonemapped_texture,
+                &self.buffers,
             );
             self.screen_pass
-                .recreate_bind_group(&self.device, &self.denoised_texture);
+                .recreate_bind_group(&self.device, &self.tonemapped_texture);
         }
 
         self.reset_frame_counter();
     }
 
+    fn recreate_temporal_bind_group(&mut self) {
+        let textures = TemporalTextures {
+            position: &self.position_texture,
+            normal: &self.normal_texture,
+            prev_position: &self.prev_position_texture,
+            prev_normal: &self.prev_normal_texture,
+            prev_result: &self.prev_result_texture,
+            prev_history: &self.prev_history_texture,
+            reprojected: &self.reprojected_texture,
+            history: &self.history_texture,
+        };
+        self.temporal_pass
+            .recreate_bind_group(&self.device, &textures, &self.buffers);
+    }
+
+    //= DENOISER ==================================================================================
+
+    /// How many times the à-trous filter runs, each one blurring twice as far as the previous,
+    /// up to `MAX_DENOISER_ITERATIONS`. Zero leaves the image noisy.
+    pub fn set_denoiser_iterations(&mut self, iterations: u32) {
+        let iterations = iterations.min(MAX_DENOISER_ITERATIONS);
+        if iterations != self.denoiser_pass.iterations {
+            self.denoiser_pass.iterations = iterations;
+            self.recreate_denoiser_bind_groups();
+        }
+    }
+
+    #[must_use]
+    pub const fn denoiser_iterations(&self) -> u32 {
+        self.denoiser_pass.iterations
+    }
+
+    fn recreate_denoiser_bind_groups(&mut self) {
+        let textures = DenoiserTextures {
+            result: &self.result_texture,
+            normal: &self.normal_texture,
+            position: &self.position_texture,
+            history: &self.history_texture,
+            denoised: &self.denoised_texture,
+            swap: &self.denoiser_swap_texture,
+        };
+        self.denoiser_pass
+            .recreate_bind_groups(&self.device, &textures);
+    }
+
     //= UPDATE =====================================================================================
 
+    /// Drops the samples accumulated so far, the camera moving doesn't need it since
+    /// they're reprojected, unlike the world changing.
     pub const fn reset_frame_counter(&mut self) {
         self.frame_data.reset();
     }
@@ -220,44 +436,94 @@ impl Renderer {
             self.write_frame_data(&self.frame_data);
             self.frame_data.increment();
             self.write_camera(&camera);
+            self.buffers
+                .prev_camera_buffer
+                .write(&self.queue, &self.prev_camera);
+            self.prev_camera = camera;
         }
 
         let workgroups = surface_size / 8;
         self.ray_tracer_pass.encode_pass(&mut encoder, workgroups);
+        self.temporal_pass.encode_pass(&mut encoder, workgroups);
         self.path_tracer_pass.encode_pass(&mut encoder, workgroups);
         self.denoiser_pass.encode_pass(&mut encoder, workgroups);
+        self.tonemap_pass.encode_pass(&mut encoder, workgroups);
         self.screen_pass.encode_pass(&mut encoder, &output_view);
 
-        encoder.copy_texture_to_texture(
-            wgpu::TexelCopyTextureInfo {
-                texture: &self.result_texture.handle,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            wgpu::TexelCopyTextureInfo {
-                texture: &self.prev_result_texture.handle,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            wgpu::Extent3d {
-                width: u32::from(self.result_texture.size().x),
-                height: u32::from(self.result_texture.size().y),
-                depth_or_array_layers: 1,
-            },
-        );
+        // Kept for the reprojection of the next frame.
+        self.result_texture
+            .copy_to(&mut encoder, &self.prev_result_texture);
+        self.history_texture
+            .copy_to(&mut encoder, &self.prev_history_texture);
+        self.normal_texture
+            .copy_to(&mut encoder, &self.prev_normal_texture);
+        self.position_texture
+            .copy_to(&mut encoder, &self.prev_position_texture);
 
         self.submit_once(encoder.finish());
 
         profiling::scope!("output_texture.present()");
-        {
+        if let Some(output_texture) = output_texture {
             output_texture.present();
         }
 
         Ok(())
     }
 
+    /// Reads back the last image drawn by a headless renderer, as RGBA rows from top to
+    /// bottom, encoded in sRGB like a window would show it.
+    ///
+    /// Fails for the renderers drawing on a window, whose images can't be read back.
+    pub fn read_output(&self) -> Result<Vec<u8>, String> {
+        match &self.target {
+            Target::Texture(texture) => texture.read(&self.device, &self.queue),
+            Target::Surface(_) => Err("The output of a window can't be read back".to_string()),
+        }
+    }
+
+    //= CAPTURE ====================================================================================
+
+    /// Saves the last image held by one of the textures as a PNG file at `path`.
+    ///
+    /// The frame of a window is gone once presented, so it's drawn again from the tonemapped
+    /// texture into a texture of the same format.
+    pub fn capture(&self, target: CaptureTarget, path: &Path) -> Result<(), String> {
+        profiling::scope!("Renderer.capture()");
+        let (texels, format) = match target {
+            CaptureTarget::Frame => match &self.target {
+                Target::Texture(texture) => (self.read_output()?, texture.handle.format()),
+                Target::Surface(_) => {
+                    let texture = TextureHandler::new(
+                        &self.device,
+                        self.surface_size(),
+                        self.surface_config.format,
+                        OUTPUT_TEX_USAGES,
+                    );
+                    let mut encoder = self.create_command_encoder();
+                    self.screen_pass.encode_pass(&mut encoder, &texture.view);
+                    self.queue.submit(iter::once(encoder.finish()));
+                    (
+                        texture.read(&self.device, &self.queue)?,
+                        self.surface_config.format,
+                    )
+                }
+            },
+            CaptureTarget::Result => self.read_texture(&self.result_texture)?,
+            CaptureTarget::Denoised => self.read_texture(&self.denoised_texture)?,
+            CaptureTarget::Tonemapped => self.read_texture(&self.tonemapped_texture)?,
+            CaptureTarget::Normal => self.read_texture(&self.normal_texture)?,
+        };
+        let pixels = to_rgba8(format, target == CaptureTarget::Normal, texels)?;
+        write_png(path, self.surface_size(), &pixels)
+    }
+
+    fn read_texture(&self, texture: &TextureHandler) -> Result<(Vec<u8>, TextureFormat), String> {
+        Ok((
+            texture.read(&self.device, &self.queue)?,
+            texture.handle.format(),
+        ))
+    }
+
     //= SURFACE TEXTURE ============================================================================
 
     pub const fn surface_size(&self) -> U16Vec2 {
@@ -267,18 +533,24 @@ impl Renderer {
         )
     }
 
-    pub fn get_output(&self) -> (SurfaceTexture, TextureView) {
+    /// The texture to draw the next image on and its view,
+    /// the surface texture must be presented once drawn, if there is a surface.
+    pub fn get_output(&self) -> (Option<SurfaceTexture>, TextureView) {
         #[allow(clippy::needless_pass_by_value)]
         fn panic_error(current: CurrentSurfaceTexture) -> ! {
             panic!("Failed to get the current texture: {current:?}");
         }
 
-        let output = match self.surface.get_current_texture() {
+        let surface = match &self.target {
+            Target::Surface(surface) => surface,
+            Target::Texture(texture) => return (None, texture.view.clone()),
+        };
+        let output = match surface.get_current_texture() {
             CurrentSurfaceTexture::Success(o) => o,
             CurrentSurfaceTexture::Suboptimal(_)
             | CurrentSurfaceTexture::Outdated
             | CurrentSurfaceTexture::Lost
-            | CurrentSurfaceTexture::Validation => match self.surface.get_current_texture() {
+            | CurrentSurfaceTexture::Validation => match surface.get_current_texture() {
                 CurrentSurfaceTexture::Success(o) => o,
                 c => panic_error(c),
             },
@@ -290,6 +562,86 @@ impl Renderer {
         let view = output
             .texture
             .create_view(&TextureViewDescriptor::default());
-        (output, view)
+        (Some(output), view)
+    }
+}
+
+//= TESTS ==========================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{VOXEL_MATERIALS, Voxel};
+
+    use glam::{IVec3, Mat4, Vec3};
+
+    /// A world of 16 voxels per side, stone below half its height and air above.
+    fn half_stone_world() -> Vec<Node> {
+        let mut nodes = vec![Node::new_split(1)];
+        for i in 0..8 {
+            let is_top = i >> 1 & 1 == 1;
+            nodes.push(Node::new_leaf(if is_top {
+                Voxel::AIR
+            } else {
+                Voxel::STONE
+            }));
+        }
+        nodes
+    }
+
+    #[test]
+    fn headless_renderer_draws_a_frame() {
+        if let Err(e) = request_headless_adapter(&create_instance()) {
+            eprintln!("Skipped, no adapter to render with: {e}");
+            return;
+        }
+        let (width, height) = (32, 16);
+        let mut renderer = Renderer::new_headless(width, height, 64).unwrap();
+
+        let nodes = half_stone_world();
+        renderer.write_nodes(0, &nodes).unwrap();
+        renderer.write_world_data(&WorldData::new(IVec3::ZERO, 16));
+        renderer.write_voxel_materials(0, VOXEL_MATERIALS).unwrap();
+        renderer.write_settings(&Settings {
+            max_ray_bounces: 4,
+            samples_per_pixel: 1,
+            sky_color: [0.81, 0.93, 1.0],
+            sun_pos: [0.0, 500.0, 0.0],
+            ..Settings::default()
+        });
+        let proj_size = U16Vec2::new(width, height).as_vec2();
+        let pos = Vec3::new(8.0, 12.0, 8.0);
+        let camera = Camera {
+            pos,
+            inv_view_mat: Mat4::from_translation(pos),
+            inv_proj_mat: Mat4::perspective_rh(
+                90f32.to_radians(),
+                proj_size.x / proj_size.y,
+                0.001,
+                1000.0,
+            )
+            .inverse(),
+            proj_size,
+            ..Default::default()
+        };
+        for _ in 0..4 {
+            renderer.update(camera).unwrap();
+        }
+
+        let pixels = renderer.read_output().unwrap();
+        assert_eq!(pixels.len(), usize::from(width) * usize::from(height) * 4);
+        assert!(pixels.chunks_exact(4).all(|pixel| pixel[3] == u8::MAX));
+
+        // The top row looks at the blue sky, the bottom row at the stone lit by it.
+        let row_luminance = |y: usize| -> u32 {
+            let row = &pixels[y * usize::from(width) * 4..][..usize::from(width) * 4];
+            row.chunks_exact(4)
+                .map(|p| u32::from(p[0]) + u32::from(p[1]) + u32::from(p[2]))
+                .sum()
+        };
+        assert!(row_luminance(0) > row_luminance(usize::from(height) - 1));
+        let sky = &pixels[..4];
+        assert!(sky[2] > sky[0], "sky {sky:?}");
+        assert!(sky[0] > 0);
     }
 }
@@ -1,45 +1,136 @@
-@group(0) @binding(0) var result_texture_: texture_2d<f32>;
-@group(0) @binding(1) var denoised_texture_: texture_storage_2d<rgba8unorm, write>;
+// SVGF-style denoiser: the `variance` entry estimates the noise of every pixel, then the
+// `atrous` entry runs once per iteration, blurring with a sparser 5x5 kernel each time while the
+// G-buffer and the variance stop it at the edges.
+// https://research.nvidia.com/publication/2017-07_spatiotemporal-variance-guided-filtering-real-time-reconstruction-path-traced
 
-const KERNEL_SIZE: i32 = 1;
+struct AtrousStep {
+    step: u32,
+}
+
+// For `variance` the accumulated colors with the luminance second moment in alpha,
+// for `atrous` the output of the previous iteration with the variance in alpha.
+@group(0) @binding(0) var input_texture_: texture_2d<f32>;
+@group(0) @binding(1) var output_texture_: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(2) var normal_texture_: texture_2d<f32>;
+@group(0) @binding(3) var position_texture_: texture_2d<f32>;
+@group(0) @binding(4) var history_texture_: texture_2d<f32>;
+@group(0) @binding(5) var<uniform> atrous_: AtrousStep;
+
+// Below these accumulated frames the variance is estimated from the neighbours.
+const MIN_HISTORY: f32 = 4.0;
+const PHI_NORMAL: f32 = 128.0;
+const PHI_PLANE: f32 = 0.1;
+const PHI_LUMINANCE: f32 = 4.0;
+
+fn luminance(color: vec3f) -> f32 {
+    return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
+
+fn in_screen(pos: vec2i) -> bool {
+    return all(pos >= vec2(0)) && all(pos < vec2i(textureDimensions(input_texture_)));
+}
 
-fn guassian_color_weight(x: vec3f, y: vec3f) -> f32 {
-    let dist_sq = f32(dot(x - y, x - y));
-    // (2.0 * sigma * sigma), but sigma is one, so 2.0
-    return exp(-dist_sq / 2.0f);
+fn load_normal(pos: vec2i) -> vec3f {
+    return textureLoad(normal_texture_, pos, 0).xyz * 2.0 - 1.0;
 }
 
-fn guassian_spatial_weight(x: vec2i, y: vec2i) -> f32 {
-    let dist_sq = f32(dot(x - y, x - y));
-    // 2.0 * sigma * sigma, but sigma space is one, so 2.0
-    return exp(-dist_sq / 2.0f);
+// How much the sample at `q` lies on the same surface of the one at `position`.
+fn geometry_weight(normal: vec3f, position: vec3f, q: vec2i) -> f32 {
+    let q_position = textureLoad(position_texture_, q, 0);
+    if (q_position.w < 0.0) {
+        return 0.0;
+    }
+    let normal_weight = pow(max(dot(normal, load_normal(q)), 0.0), PHI_NORMAL);
+    let plane_dist = abs(dot(normal, q_position.xyz - position));
+    return normal_weight * exp(-plane_dist / PHI_PLANE);
 }
 
 @compute @workgroup_size(8, 8, 1)
-fn update(@builtin(global_invocation_id) inv_id: vec3u) {
+fn variance(@builtin(global_invocation_id) inv_id: vec3u) {
     let screen_pos = vec2i(inv_id.xy);
-    let center_color: vec3f = textureLoad(result_texture_, screen_pos, 0).rgb;
-    var result = vec3(0.0);
+    let center = textureLoad(input_texture_, screen_pos, 0);
+    let position = textureLoad(position_texture_, screen_pos, 0);
+    let history = textureLoad(history_texture_, screen_pos, 0).r;
+
+    var moments = vec2(luminance(center.rgb), center.a);
+    if (history < MIN_HISTORY && position.w >= 0.0) {
+        let normal = load_normal(screen_pos);
+        var weight_sum = 1.0;
+        for (var y = -1; y <= 1; y += 1) {
+            for (var x = -1; x <= 1; x += 1) {
+                let q = screen_pos + vec2(x, y);
+                if ((x == 0 && y == 0) || !in_screen(q)) {
+                    continue;
+                }
+                let weight = geometry_weight(normal, position.xyz, q);
+                let sample = textureLoad(input_texture_, q, 0);
+                moments += vec2(luminance(sample.rgb), sample.a) * weight;
+                weight_sum += weight;
+            }
+        }
+        moments /= weight_sum;
+    }
+
+    // The noise left in the average shrinks with the frames accumulated.
+    let variance = max(moments.y - moments.x * moments.x, 0.0) / history;
+    textureStore(output_texture_, screen_pos, vec4(center.rgb, variance));
+}
+
+// Gaussian blur of the variance, steadier to guide the luminance weights.
+fn filtered_variance(screen_pos: vec2i) -> f32 {
+    var kernel = array(0.25, 0.125, 0.0625);
+    var result = 0.0;
     var weight_sum = 0.0;
-    
-    var i: i32 = -KERNEL_SIZE;
-    while i <= KERNEL_SIZE {
-        var j: i32 = -KERNEL_SIZE;
-        while j <= KERNEL_SIZE {
-            let current_coords: vec2i = screen_pos + vec2(i, j);
-            let current_color: vec3f = textureLoad(result_texture_, current_coords, 0).rgb;
-            
-            let color_weight: f32 = guassian_color_weight(center_color, current_color);
-            let spatial_weight: f32 = guassian_spatial_weight(screen_pos, current_coords);
-            let weight: f32 = color_weight * spatial_weight;
-            
-            result += current_color * weight;
+    for (var y = -1; y <= 1; y += 1) {
+        for (var x = -1; x <= 1; x += 1) {
+            let q = screen_pos + vec2(x, y);
+            if (!in_screen(q)) {
+                continue;
+            }
+            let weight = kernel[abs(x) + abs(y)];
+            result += textureLoad(input_texture_, q, 0).a * weight;
+            weight_sum += weight;
+        }
+    }
+    return result / weight_sum;
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn atrous(@builtin(global_invocation_id) inv_id: vec3u) {
+    let screen_pos = vec2i(inv_id.xy);
+    let center = textureLoad(input_texture_, screen_pos, 0);
+    let position = textureLoad(position_texture_, screen_pos, 0);
+    if (position.w < 0.0) {
+        // The sky has nothing to denoise.
+        textureStore(output_texture_, screen_pos, center);
+        return;
+    }
+    let normal = load_normal(screen_pos);
+    let center_luminance = luminance(center.rgb);
+    let luminance_scale = PHI_LUMINANCE * sqrt(filtered_variance(screen_pos)) + 1e-4;
+
+    // B3 spline, from the centre outwards.
+    var kernel = array(0.375, 0.25, 0.0625);
+    var color = center.rgb * kernel[0] * kernel[0];
+    var variance = center.a * kernel[0] * kernel[0] * kernel[0] * kernel[0];
+    var weight_sum = kernel[0] * kernel[0];
+    for (var y = -2; y <= 2; y += 1) {
+        for (var x = -2; x <= 2; x += 1) {
+            let q = screen_pos + vec2(x, y) * i32(atrous_.step);
+            if ((x == 0 && y == 0) || !in_screen(q)) {
+                continue;
+            }
+            let sample = textureLoad(input_texture_, q, 0);
+            let luminance_weight = exp(-abs(luminance(sample.rgb) - center_luminance) / luminance_scale);
+            let weight = kernel[abs(x)] * kernel[abs(y)] *
+                geometry_weight(normal, position.xyz, q) *
+                luminance_weight;
+
+            color += sample.rgb * weight;
+            variance += sample.a * weight * weight;
             weight_sum += weight;
-            
-            j += 1;
         }
-        i += 1;
     }
 
-    textureStore(denoised_texture_, screen_pos, vec4(result / weight_sum, 1.0));
+    textureStore(output_texture_, screen_pos, vec4(color / weight_sum, variance / (weight_sum * weight_sum)));
 }
@@ -14,8 +14,10 @@ struct Settings {
     max_ray_bounces: u32,
     samples_per_pixel: u32,
     sun_intensity: f32,
+    exposure: f32,
     sky_color: vec3f,
     sun_pos: vec3f,
+    tonemapper: u32,
 }
 
 struct World {
@@ -28,8 +30,9 @@ fn get_bits(field: u32, len: u32, offset: u32) -> u32 {
     return (field & mask) >> offset;
 }
 
+// Keep the voxel bits in sync with `Voxel::BITS`.
 fn node_voxel(node_idx: u32) -> u32 {
-    return get_bits(nodes_[node_idx], 8u, 0u);
+    return get_bits(nodes_[node_idx], 16u, 0u);
 }
 fn node_is_split(node_idx: u32) -> bool {
     return get_bits(nodes_[node_idx], 1u, 31u) == 1u;
@@ -48,14 +51,16 @@ struct Material {
     polish_scatter: f32,
 }
 
-@group(0) @binding(0) var output_texture_: texture_storage_2d<rgba8unorm, write>;
+@group(0) @binding(0) var output_texture_: texture_storage_2d<rgba32float, write>;
 @group(0) @binding(1) var<uniform> cam_data_: CamData;
 @group(0) @binding(2) var<uniform> settings_: Settings;
 @group(0) @binding(3) var<storage, read> nodes_: array<u32>;
 @group(0) @binding(4) var<storage, read> voxel_mats_: array<Material>;
 @group(0) @binding(5) var<uniform> frame_data_: FrameData;
 @group(0) @binding(6) var<uniform> world_: World;
+// The samples of the previous frames, reprojected to this one.
 @group(0) @binding(7) var prev_output_texture_: texture_2d<f32>;
+@group(0) @binding(8) var history_texture_: texture_2d<f32>;
 
 fn rng_next(state: ptr<function, u32>) -> f32 {
     *state = *state * 747796405u + 2891336453u;
@@ -306,8 +311,10 @@ fn update(@builtin(global_invocation_id) inv_id: vec3u) {
     color /= f32(ray_count);
 
     let old_render = textureLoad(prev_output_texture_, screen_pos, 0);
-    let weight = 1.0 / f32(frame_data_.cumulator + 1u);
-    let result = old_render * (1.0 - weight) + vec4(color * weight, 1.0);
+    let weight = 1.0 / textureLoad(history_texture_, screen_pos, 0).r;
+    // The alpha keeps the second moment of the luminance, for the variance of the denoiser.
+    let luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    let result = old_render * (1.0 - weight) + vec4(color, luminance * luminance) * weight;
 
     textureStore(output_texture_, screen_pos, result);
 }
@@ -14,8 +14,10 @@ struct Settings {
     max_ray_bounces: u32,
     samples_per_pixel: u32,
     sun_intensity: f32,
+    exposure: f32,
     sky_color: vec3f,
     sun_pos: vec3f,
+    tonemapper: u32,
 }
 
 struct World {
@@ -28,8 +30,9 @@ fn get_bits(field: u32, len: u32, offset: u32) -> u32 {
     return (field & mask) >> offset;
 }
 
+// Keep the voxel bits in sync with `Voxel::BITS`.
 fn node_voxel(node_idx: u32) -> u32 {
-    return get_bits(nodes_[node_idx], 8u, 0u);
+    return get_bits(nodes_[node_idx], 16u, 0u);
 }
 fn node_is_split(node_idx: u32) -> bool {
     return get_bits(nodes_[node_idx], 1u, 31u) == 1u;
@@ -52,12 +55,19 @@ struct Material {
 @group(0) @binding(1) var<uniform> cam_data_: CamData;
 @group(0) @binding(2) var<storage, read> nodes_: array<u32>;
 @group(0) @binding(3) var<uniform> world_: World;
+@group(0) @binding(4) var position_texture_: texture_storage_2d<rgba32float, write>;
 
 struct Ray {
     origin: vec3f,
     dir: vec3f,
 }
 
+struct HitResult {
+    hit: bool,
+    norm: vec3f,
+    pos: vec3f,
+}
+
 struct FoundNode {
     idx: u32,
     min: vec3f,
@@ -98,7 +108,7 @@ fn find_node(pos: vec3f) -> FoundNode {
     return out;
 }
 
-fn ray_normal(start_ray: Ray) -> vec3f {
+fn ray_world(start_ray: Ray) -> HitResult {
     let dir = start_ray.dir;
     let mask = vec3f(f32(dir.x >= 0.0), f32(dir.y >= 0.0), f32(dir.z >= 0.0));
     let imask = 1.0 - mask;
@@ -108,8 +118,10 @@ fn ray_normal(start_ray: Ray) -> vec3f {
     let world_min = world_.min;
     let world_max = world_min + vec3f(world_.size);
 
+    var result: HitResult;
+
     if (any(ray_pos <= world_min) | any(ray_pos >= world_max)) {
-        return vec3f(0.0);
+        return result;
     }
 
     // length of a line in same direction as the ray,
@@ -168,14 +180,20 @@ fn ray_normal(start_ray: Ray) -> vec3f {
             }
         }
 
-        norm = vec3f(f32(step == axis_dist.x), f32(step == axis_dist.y), f32(step == axis_dist.z));
+        norm = vec3f(f32(step == axis_dist.x), f32(step == axis_dist.y), f32(step == axis_dist.z)) * -sign(dir);
+        ray_pos += dir * (step + 0.001) * vec3f(f32(step == axis_dist.x), f32(step == axis_dist.y), f32(step == axis_dist.z)) +
+            dir * (step) * vec3f(f32(step != axis_dist.x), f32(step != axis_dist.y), f32(step != axis_dist.z));
 
         if (any(ray_pos < world_min) | any(ray_pos >= world_max)) {
-            return norm;
+            return result;
         } // out of bounds
     } // return not air OR max steps already !!!!!!!!!!!
 
-    return norm;
+    // Running out of steps in the air is a miss, like leaving the world.
+    result.hit = voxel != 0u;
+    result.pos = ray_pos;
+    result.norm = norm;
+    return result;
 }
 
 fn create_ray_from_screen(screen_pos: vec2i) -> Ray {
@@ -196,7 +214,15 @@ fn create_ray_from_screen(screen_pos: vec2i) -> Ray {
 fn update(@builtin(global_invocation_id) inv_id: vec3u) {
     let screen_pos = vec2i(inv_id.xy);
     let ray = create_ray_from_screen(screen_pos);
-    let normal = ray_normal(ray);
-
-    textureStore(normal_texture_, screen_pos, vec4(normal, 0.0));
+    let rs = ray_world(ray);
+
+    // The G-buffer guiding the denoiser, the normals are encoded in 0..1 and the sky
+    // has a negative depth.
+    if (!rs.hit) {
+        textureStore(normal_texture_, screen_pos, vec4(0.5, 0.5, 0.5, 0.0));
+        textureStore(position_texture_, screen_pos, vec4(0.0, 0.0, 0.0, -1.0));
+        return;
+    }
+    textureStore(normal_texture_, screen_pos, vec4(rs.norm * 0.5 + 0.5, 1.0));
+    textureStore(position_texture_, screen_pos, vec4(rs.pos, distance(rs.pos, ray.origin)));
 }
@@ -1,6 +1,9 @@
 @group(0) @binding(0) var tex: texture_2d<f32>;
 @group(0) @binding(1) var tex_s: sampler;
 
+// Set when the target format doesn't convert the linear colors to sRGB by itself.
+override ENCODE_SRGB: bool = false;
+
 struct FsInput {
 	@builtin(position) pos: vec4f,
 	@location(0) tex_coord: vec2f,
@@ -31,11 +34,21 @@ fn vs_main(@builtin(vertex_index) index: u32) -> FsInput {
 	return out;
 }
 
+fn linear_to_srgb(color: vec3f) -> vec3f {
+	let low = color * 12.92;
+	let high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+	return select(high, low, color <= vec3(0.0031308));
+}
+
 @fragment
 fn fs_main(in: FsInput) -> @location(0) vec4f {
 	let crosshair_mask = f32(distance(vec2(0.5, 0.5), in.tex_coord) < 0.003);
-	return 
+	let color =
 		textureSample(tex, tex_s, in.tex_coord) * (1.0 - crosshair_mask) +
-		vec4(5.0, 5.0, 5.0, 1.0) * crosshair_mask
+		vec4(1.0, 1.0, 1.0, 1.0) * crosshair_mask
 	;
+	if ENCODE_SRGB {
+		return vec4(linear_to_srgb(clamp(color.rgb, vec3(0.0), vec3(1.0))), color.a);
+	}
+	return color;
 }
@@ -0,0 +1,130 @@
+// Temporal reprojection: finds where every pixel was in the previous frame and brings its
+// accumulated samples along, so the path tracer keeps converging while the camera moves.
+// Only the pixels that weren't visible before restart from zero.
+
+struct CamData {
+    pos: vec3f,
+    inv_view_mat: mat4x4f,
+    inv_proj_mat: mat4x4f,
+    proj_size: vec2f,
+}
+
+struct FrameData {
+    counter: u32,
+    cumulator: u32,
+}
+
+@group(0) @binding(0) var position_texture_: texture_2d<f32>;
+@group(0) @binding(1) var normal_texture_: texture_2d<f32>;
+@group(0) @binding(2) var prev_position_texture_: texture_2d<f32>;
+@group(0) @binding(3) var prev_normal_texture_: texture_2d<f32>;
+@group(0) @binding(4) var prev_result_texture_: texture_2d<f32>;
+@group(0) @binding(5) var prev_history_texture_: texture_2d<f32>;
+@group(0) @binding(6) var reprojected_texture_: texture_storage_2d<rgba32float, write>;
+@group(0) @binding(7) var history_texture_: texture_storage_2d<r32float, write>;
+@group(0) @binding(8) var<uniform> prev_cam_data_: CamData;
+@group(0) @binding(9) var<uniform> frame_data_: FrameData;
+
+// The samples of a moving pixel are resampled every frame, so its history is kept short
+// to let the blur fade away.
+const MAX_MOVING_HISTORY: f32 = 64.0;
+const MIN_NORMAL_DOT: f32 = 0.9;
+const MAX_PLANE_DIST: f32 = 0.05;
+
+fn load_normal(texture: texture_2d<f32>, pos: vec2i) -> vec3f {
+    return textureLoad(texture, pos, 0).xyz * 2.0 - 1.0;
+}
+
+// The inverse of `create_ray_from_screen` for the previous camera: the (not rounded) pixel
+// whose ray passed through `world_pos`, and whether it was in front of the camera.
+fn project_to_prev_screen(world_pos: vec3f) -> vec3f {
+    let dir = world_pos - prev_cam_data_.pos;
+    let view = prev_cam_data_.inv_view_mat;
+    let eye = mat3x3f(view[0].xyz, view[1].xyz, view[2].xyz) * dir;
+    if (eye.z >= 0.0) {
+        return vec3(0.0, 0.0, -1.0);
+    }
+    let eye_xy = eye.xy / -eye.z;
+
+    // The eye coordinates are an affine function of the clip coordinates (x, -y).
+    let proj = prev_cam_data_.inv_proj_mat;
+    let linear = mat2x2f(vec2(proj[0].x, proj[1].x), vec2(proj[0].y, proj[1].y));
+    let offset = vec2(dot(vec2(-1.0, 1.0), proj[0].zw), dot(vec2(-1.0, 1.0), proj[1].zw));
+    let clip = inverse2x2(linear) * (eye_xy - offset);
+
+    let screen = (vec2(clip.x, -clip.y) + 1.0) * 0.5 * prev_cam_data_.proj_size;
+    return vec3(screen, 1.0);
+}
+
+fn inverse2x2(m: mat2x2f) -> mat2x2f {
+    let det = determinant(m);
+    return mat2x2f(vec2(m[1].y, -m[0].y), vec2(-m[1].x, m[0].x)) * (1.0 / det);
+}
+
+// Whether the previous frame saw the same surface at `q`.
+fn is_history_valid(normal: vec3f, position: vec3f, q: vec2i) -> bool {
+    if (any(q < vec2(0)) || any(q >= vec2i(textureDimensions(prev_position_texture_)))) {
+        return false;
+    }
+    let prev_position = textureLoad(prev_position_texture_, q, 0);
+    if (prev_position.w < 0.0) {
+        return false;
+    }
+    let same_normal = dot(normal, load_normal(prev_normal_texture_, q)) >= MIN_NORMAL_DOT;
+    let same_plane = abs(dot(normal, prev_position.xyz - position)) <= MAX_PLANE_DIST;
+    return same_normal && same_plane;
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn update(@builtin(global_invocation_id) inv_id: vec3u) {
+    let screen_pos = vec2i(inv_id.xy);
+    let position = textureLoad(position_texture_, screen_pos, 0);
+
+    // After a reset nothing is valid, and the sky is the same at every sample.
+    if (frame_data_.cumulator == 0u || position.w < 0.0) {
+        textureStore(reprojected_texture_, screen_pos, vec4(0.0));
+        textureStore(history_texture_, screen_pos, vec4(1.0));
+        return;
+    }
+    let prev_screen = project_to_prev_screen(position.xyz);
+    if (prev_screen.z < 0.0) {
+        textureStore(reprojected_texture_, screen_pos, vec4(0.0));
+        textureStore(history_texture_, screen_pos, vec4(1.0));
+        return;
+    }
+
+    // Bilinear filtering of the previous frame, skipping the samples of other surfaces.
+    let normal = load_normal(normal_texture_, screen_pos);
+    let base = vec2i(floor(prev_screen.xy));
+    let t = fract(prev_screen.xy);
+    var history = vec4(0.0);
+    var history_len = 0.0;
+    var weight_sum = 0.0;
+    for (var y = 0; y <= 1; y += 1) {
+        for (var x = 0; x <= 1; x += 1) {
+            let q = base + vec2(x, y);
+            if (!is_history_valid(normal, position.xyz, q)) {
+                continue;
+            }
+            let weight = mix(1.0 - t.x, t.x, f32(x)) * mix(1.0 - t.y, t.y, f32(y));
+            history += textureLoad(prev_result_texture_, q, 0) * weight;
+            history_len += textureLoad(prev_history_texture_, q, 0).r * weight;
+            weight_sum += weight;
+        }
+    }
+    if (weight_sum < 0.01) {
+        // Disoccluded, this pixel wasn't visible in the previous frame.
+        textureStore(reprojected_texture_, screen_pos, vec4(0.0));
+        textureStore(history_texture_, screen_pos, vec4(1.0));
+        return;
+    }
+    history /= weight_sum;
+    history_len /= weight_sum;
+
+    if (distance(prev_screen.xy, vec2f(screen_pos)) > 0.01) {
+        history_len = min(history_len, MAX_MOVING_HISTORY);
+    }
+    textureStore(reprojected_texture_, screen_pos, history);
+    // Counting the sample the path tracer adds in this frame.
+    textureStore(history_texture_, screen_pos, vec4(history_len + 1.0));
+}
@@ -0,0 +1,85 @@
+struct Settings {
+    max_ray_bounces: u32,
+    samples_per_pixel: u32,
+    sun_intensity: f32,
+    exposure: f32,
+    sky_color: vec3f,
+    sun_pos: vec3f,
+    tonemapper: u32,
+}
+
+@group(0) @binding(0) var hdr_texture_: texture_2d<f32>;
+@group(0) @binding(1) var tonemapped_texture_: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(2) var<uniform> settings_: Settings;
+
+const TONEMAPPER_REINHARD: u32 = 0u;
+const TONEMAPPER_ACES: u32 = 1u;
+const TONEMAPPER_AGX: u32 = 2u;
+
+fn reinhard(color: vec3f) -> vec3f {
+    return color / (1.0 + color);
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve.
+fn aces(color: vec3f) -> vec3f {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((color * (a * color + b)) / (color * (c * color + d) + e), vec3(0.0), vec3(1.0));
+}
+
+// Benjamin Wrensch's fit of the AgX base curve, from Blender.
+fn agx_contrast(x: vec3f) -> vec3f {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    return 15.5 * x4 * x2
+        - 40.14 * x4 * x
+        + 31.96 * x4
+        - 6.868 * x2 * x
+        + 0.4298 * x2
+        + 0.1191 * x
+        - 0.00232;
+}
+
+fn agx(color: vec3f) -> vec3f {
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+    let inset = mat3x3f(
+        vec3(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+        vec3(0.0784335999999992, 0.878468636469772, 0.0784336),
+        vec3(0.0792237451477643, 0.0791661274605434, 0.879142973793104),
+    );
+    let outset = mat3x3f(
+        vec3(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+        vec3(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+        vec3(-0.0990297440797205, -0.0989611768448433, 1.15107367264116),
+    );
+
+    let log_color = clamp(log2(max(inset * color, vec3(1e-10))), vec3(min_ev), vec3(max_ev));
+    let encoded = agx_contrast((log_color - min_ev) / (max_ev - min_ev));
+    // The curve gives a display encoded color, back to linear like the other tonemappers.
+    return pow(max(outset * encoded, vec3(0.0)), vec3(2.2));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn update(@builtin(global_invocation_id) inv_id: vec3u) {
+    let screen_pos = vec2i(inv_id.xy);
+    let color = textureLoad(hdr_texture_, screen_pos, 0).rgb * exp2(settings_.exposure);
+
+    var result: vec3f;
+    switch settings_.tonemapper {
+        case TONEMAPPER_REINHARD: {
+            result = reinhard(color);
+        }
+        case TONEMAPPER_AGX: {
+            result = agx(color);
+        }
+        default: {
+            result = aces(color);
+        }
+    }
+
+    textureStore(tonemapped_texture_, screen_pos, vec4(result, 1.0));
+}
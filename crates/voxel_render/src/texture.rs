@@ -2,19 +2,39 @@
 
 use glam::U16Vec2;
 use wgpu::{
-    AddressMode, Device, Extent3d, FilterMode, MipmapFilterMode, Sampler, SamplerDescriptor,
+    AddressMode, BufferDescriptor, BufferUsages, COPY_BYTES_PER_ROW_ALIGNMENT, CommandEncoder,
+    CommandEncoderDescriptor, Device, Extent3d, FilterMode, MapMode, MipmapFilterMode, PollType,
+    Queue, Sampler, SamplerDescriptor, TexelCopyBufferInfo, TexelCopyBufferLayout,
     TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView,
     TextureViewDescriptor,
 };
 
+use std::iter;
+use std::sync::mpsc::channel;
+
 //= CONSTANTS ======================================================================================
 
-pub const RESULT_TEX_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+/// The path tracer accumulates the samples in full precision, so they keep adding up
+/// after thousands of frames, and the emissive voxels don't clip.
+pub const RESULT_TEX_FORMAT: TextureFormat = TextureFormat::Rgba32Float;
+/// The HDR images after the path tracer, filterable unlike `RESULT_TEX_FORMAT`.
+pub const HDR_TEX_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+/// The normals of the first hits, encoded in the 0..1 range.
+pub const NORMAL_TEX_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+/// The position of the first hits and their distance from the camera.
+pub const POSITION_TEX_FORMAT: TextureFormat = TextureFormat::Rgba32Float;
+/// How many samples each pixel has accumulated, the previous frames included.
+pub const HISTORY_TEX_FORMAT: TextureFormat = TextureFormat::R32Float;
 pub const RESULT_TEX_USAGES: TextureUsages = TextureUsages::COPY_DST
     .union(TextureUsages::COPY_SRC)
     .union(TextureUsages::STORAGE_BINDING)
     .union(TextureUsages::TEXTURE_BINDING);
 
+/// The final image when there is no window, encoded like the swapchain of one.
+pub const OUTPUT_TEX_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+pub const OUTPUT_TEX_USAGES: TextureUsages =
+    TextureUsages::RENDER_ATTACHMENT.union(TextureUsages::COPY_SRC);
+
 //= TEXTURE ========================================================================================
 
 pub(super) struct TextureHandler {
@@ -67,8 +87,66 @@ impl TextureHandler {
         }
     }
 
-    pub(crate) fn size(&self) -> U16Vec2 {
+    /// Copies the whole texture into `dst`, of the same size and format.
+    pub(crate) fn copy_to(&self, encoder: &mut CommandEncoder, dst: &Self) {
+        encoder.copy_texture_to_texture(
+            self.handle.as_image_copy(),
+            dst.handle.as_image_copy(),
+            self.handle.size(),
+        );
+    }
+
+    /// Copies the texture back from the GPU, waiting for the work submitted so far.
+    ///
+    /// The texture must have a color format and `TextureUsages::COPY_SRC`,
+    /// the rows are returned top to bottom without padding.
+    pub(crate) fn read(&self, device: &Device, queue: &Queue) -> Result<Vec<u8>, String> {
         let size = self.handle.size();
-        U16Vec2::new(size.width as u16, size.height as u16)
+        let Some(texel_len) = self.handle.format().block_copy_size(None) else {
+            return Err(format!("Can't read a {:?} texture", self.handle.format()));
+        };
+        let row_len = size.width * texel_len;
+        let padded_row_len = row_len.next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("texture-readback.buffer"),
+            size: u64::from(padded_row_len * size.height),
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+        encoder.copy_texture_to_buffer(
+            self.handle.as_image_copy(),
+            TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_len),
+                    rows_per_image: Some(size.height),
+                },
+            },
+            size,
+        );
+        queue.submit(iter::once(encoder.finish()));
+
+        let (sender, receiver) = channel();
+        buffer.map_async(MapMode::Read, .., move |result| drop(sender.send(result)));
+        device
+            .poll(PollType::wait_indefinitely())
+            .map_err(|e| e.to_string())?;
+        receiver
+            .recv()
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+
+        let mapped = buffer.get_mapped_range(..);
+        let result = mapped
+            .chunks_exact(padded_row_len as usize)
+            .flat_map(|row| &row[..row_len as usize])
+            .copied()
+            .collect();
+        drop(mapped);
+        buffer.unmap();
+        Ok(result)
     }
 }
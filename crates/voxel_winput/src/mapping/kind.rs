@@ -13,5 +13,9 @@ pub enum InputKind {
     Jump,
     SlowPace,
     Flying,
+    Undo,
+    Redo,
+    WorldStats,
+    Screenshot,
     Max,
 }
@@ -1,13 +1,20 @@
 //= IMPORTS ========================================================================================
 
 use crate::player::Player;
-use crate::world::{NodeSeq, World, WorldGen};
+use crate::world::{BiomeRegistry, EditJournal, MAX_FRAGMENTATION, World, WorldGen, WorldStreamer};
 
+use voxel_config::Config;
 use voxel_math::dda::HitResult;
-use voxel_render::{Material, Renderer, Settings, VOXEL_MATERIALS, Voxel, WorldData};
+use voxel_render::{
+    CaptureTarget, Material, Renderer, Settings, VOXEL_MATERIALS, Voxel, WorldData,
+};
 use voxel_winput::{mapping::InputKind, window::Window};
 
-use glam::{IVec3, Vec3};
+use glam::{IVec2, IVec3, Vec3, Vec3Swizzles};
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 //= INVENTORY ======================================================================================
 
@@ -34,13 +41,16 @@ pub static INVENTORY: &[Voxel] = &[
     Voxel::GOLD,
     Voxel::MIRROR,
     Voxel::BRIGHT,
+    Voxel::COAL,
+    Voxel::IRON,
+    Voxel::COPPER,
+    Voxel::CRYSTAL,
 ];
 
 #[derive(Default)]
 pub struct UpdateResult {
     pub hit_result: Option<HitResult>,
     pub world_changed: bool,
-    pub player_moved: bool,
 }
 
 pub struct GameState {
@@ -48,16 +58,25 @@ pub struct GameState {
     pub inv_sel: u8,
 
     pub settings: Settings,
-    pub world_gen: WorldGen,
+    pub world_gen: Arc<WorldGen>,
+    pub streamer: WorldStreamer,
+    pub journal: EditJournal,
+    /// The world is deduplicated in place of being compacted, see `World::deduplicate`.
+    pub deduplicate_world: bool,
     pub voxel_materials: Vec<Material>,
 }
 
 impl GameState {
-    pub fn new(world: &mut World, renderer: &Renderer) -> Self {
-        let player = Player::new(
-            Vec3::new(world.size as f32 * 0.5, 100.0, world.size as f32 * 0.5),
-            0.3, // was 0.2
-        );
+    pub fn new(
+        world: &mut World,
+        renderer: &Renderer,
+        biomes: BiomeRegistry,
+        config: &Config,
+    ) -> Self {
+        let threads = match config.generation_threads {
+            0 => num_cpus::get(),
+            threads => threads as usize,
+        };
 
         let sun_pos = Vec3::new(
             0.0f32.to_radians().sin() * 500.0,
@@ -74,14 +93,40 @@ impl GameState {
             ..Settings::default()
         };
 
-        let world_gen = WorldGen::new(fastrand::i64(..));
-        world_gen.populate(IVec3::ZERO, IVec3::splat(world.size as i32), world);
+        world.seed = fastrand::i64(..);
+        let mut world_gen = WorldGen::new(world.seed, biomes);
+        world_gen.threads = threads;
+        if let Err(e) = world_gen.populate(world.min(), world.max(), world) {
+            log::error!("World generation stopped: {e:?}");
+        }
+        if config.deduplicate_world {
+            let saved = world.deduplicate();
+            log::info!("World deduplicated, {saved} nodes saved");
+        }
+        let world_gen = Arc::new(world_gen);
+        let streamer = WorldStreamer::new(Arc::clone(&world_gen), world, threads);
+
+        let center = world.min + IVec3::splat(world.size as i32 / 2);
+        let spawn = (center.xz() + IVec2::from_array(config.spawn_offset))
+            .clamp(world.min.xz(), world.max().xz() - IVec2::ONE);
+        let spawn_y = world.surface_at(spawn.x, spawn.y).unwrap_or_else(|_| {
+            log::warn!("No room to spawn at {spawn}, spawning on top of the world");
+            world.max().y
+        });
+        let player = Player::new(
+            Vec3::new(spawn.x as f32 + 0.5, spawn_y as f32, spawn.y as f32 + 0.5),
+            0.3, // was 0.2
+        );
 
-        renderer.write_nodes(0, world.nodes());
+        if let Err(e) = renderer.sync_world(world) {
+            log::error!("{e}");
+        }
         renderer.write_settings(&settings);
         renderer.write_world_data(&WorldData::new(world.min, world.size));
         let voxel_materials = VOXEL_MATERIALS.to_vec();
-        renderer.write_voxel_materials(0, &voxel_materials);
+        if let Err(e) = renderer.write_voxel_materials(0, &voxel_materials) {
+            log::error!("{e}");
+        }
 
         Self {
             player,
@@ -89,6 +134,9 @@ impl GameState {
 
             settings,
             world_gen,
+            streamer,
+            journal: EditJournal::default(),
+            deduplicate_world: config.deduplicate_world,
             voxel_materials,
         }
     }
@@ -119,10 +167,10 @@ impl GameState {
             world.clear_node(node(0, 1, 1));
 
             world.min.x -= world.size as i32 / 2;
-            self.world_gen.populate(
+            self.streamer.require(
                 world.min(),
                 world.max() - IVec3::X * world.size as i32 / 2,
-                world,
+                self.player.position,
             );
         }
         if (self.player.position.x as i32) > world.max().x - edge_dist {
@@ -138,10 +186,10 @@ impl GameState {
             world.clear_node(node(1, 1, 1));
 
             world.min.x += world.size as i32 / 2;
-            self.world_gen.populate(
+            self.streamer.require(
                 world.min() + IVec3::X * world.size as i32 / 2,
                 world.max(),
-                world,
+                self.player.position,
             );
         }
         if (self.player.position.z as i32) < world.min().z + edge_dist {
@@ -157,10 +205,10 @@ impl GameState {
             world.clear_node(node(1, 1, 0));
 
             world.min.z -= world.size as i32 / 2;
-            self.world_gen.populate(
+            self.streamer.require(
                 world.min(),
                 world.max() - IVec3::Z * world.size as i32 / 2,
-                world,
+                self.player.position,
             );
         }
         if (self.player.position.z as i32) > world.max().z - edge_dist {
@@ -176,19 +224,50 @@ impl GameState {
             world.clear_node(node(1, 1, 1));
 
             world.min.z += world.size as i32 / 2;
-            self.world_gen.populate(
+            self.streamer.require(
                 world.min() + IVec3::Z * world.size as i32 / 2,
                 world.max(),
-                world,
+                self.player.position,
             );
         }
+
+        // The regions the player is walking toward are generated ahead of the scroll.
+        let half = IVec3::splat(world.size as i32 / 2);
+        let size = IVec3::splat(world.size as i32);
+        let prefetch_dist = world.size as i32 / 4;
+        let pos = self.player.position;
+        if (pos.x as i32) < world.min().x + prefetch_dist {
+            let (min, max) = (world.min() - half * IVec3::X, world.max() - size * IVec3::X);
+            self.streamer.prefetch(min, max, pos);
+        }
+        if (pos.x as i32) > world.max().x - prefetch_dist {
+            let (min, max) = (world.min() + size * IVec3::X, world.max() + half * IVec3::X);
+            self.streamer.prefetch(min, max, pos);
+        }
+        if (pos.z as i32) < world.min().z + prefetch_dist {
+            let (min, max) = (world.min() - half * IVec3::Z, world.max() - size * IVec3::Z);
+            self.streamer.prefetch(min, max, pos);
+        }
+        if (pos.z as i32) > world.max().z - prefetch_dist {
+            let (min, max) = (world.min() + size * IVec3::Z, world.max() + half * IVec3::Z);
+            self.streamer.prefetch(min, max, pos);
+        }
+
         if world_moved {
-            renderer.write_nodes(0, world.nodes());
+            if world.fragmentation() > MAX_FRAGMENTATION {
+                if self.deduplicate_world {
+                    world.deduplicate();
+                } else {
+                    world.compact();
+                }
+            }
             renderer.write_world_data(&WorldData::new(world.min, world.size));
         }
 
-        let prev_pos = self.player.position;
-        let prev_rot = self.player.rotation;
+        if let Err(e) = self.streamer.update(world) {
+            log::error!("World streaming stopped: {e:?}");
+        }
+
         self.player.update(window, world);
         if window.get_input_state(InputKind::InventoryPrev).is_some() {
             self.inv_sel = (self.inv_sel as i8 - 1).clamp(0, INVENTORY.len() as i8 - 1) as u8;
@@ -196,10 +275,6 @@ impl GameState {
             self.inv_sel = (self.inv_sel as i8 + 1).clamp(0, INVENTORY.len() as i8 - 1) as u8;
         }
 
-        if prev_pos != self.player.position || prev_rot != self.player.rotation {
-            output.player_moved = true;
-        }
-
         let surface_size = renderer.surface_size();
         let camera = self.player.create_camera(surface_size);
         renderer.write_camera(&camera);
@@ -230,16 +305,44 @@ impl GameState {
             _ => None,
         };
 
-        if let (Some(pos), Some(vox)) = (set_pos, set_vox) {
-            for NodeSeq { idx, count } in world.set_voxel(pos, vox).unwrap() {
-                renderer.write_nodes(
-                    idx as u64,
-                    &world.nodes()[idx as usize..idx as usize + count as usize],
-                );
+        if let (Some(pos), Some(vox)) = (set_pos, set_vox)
+            && let Err(e) = self.journal.set_voxel(world, pos, vox)
+        {
+            log::warn!("Can't edit the voxel at {pos}: {e:?}");
+        }
+        // Only on the first frame the key is down, holding it doesn't walk the whole history.
+        let pressed = |kind| {
+            window
+                .get_input_state(kind)
+                .is_some_and(|s| s.pressure_time() == 0)
+        };
+        let history = if pressed(InputKind::Undo) {
+            self.journal.undo(world)
+        } else if pressed(InputKind::Redo) {
+            self.journal.redo(world)
+        } else {
+            Ok(vec![])
+        };
+        if let Err(e) = history {
+            log::warn!("Can't undo or redo: {e:?}");
+        }
+        if pressed(InputKind::WorldStats) {
+            log::info!("World stats\n{}", world.stats());
+        }
+        if pressed(InputKind::Screenshot) {
+            match screenshot_path() {
+                Ok(path) => match renderer.capture(CaptureTarget::Frame, &path) {
+                    Ok(()) => log::info!("Screenshot saved to {}", path.display()),
+                    Err(e) => log::error!("Can't save the screenshot: {e}"),
+                },
+                Err(e) => log::error!("Can't save the screenshot: {e}"),
             }
-
-            renderer.reset_frame_counter();
         }
+        output.world_changed |= renderer.sync_world(world).unwrap_or_else(|e| {
+            // Nothing was uploaded, the nodes are tried again the next frame.
+            log::error!("{e}");
+            false
+        });
 
         if window.get_input_state(InputKind::InventoryNext).is_some()
             && (self.inv_sel as usize) < INVENTORY.len() - 1
@@ -254,3 +357,16 @@ impl GameState {
         output
     }
 }
+
+//= SCREENSHOTS ====================================================================================
+
+const SCREENSHOTS_DIR: &str = "screenshots";
+
+/// A new file in the screenshots folder, named after the current time in milliseconds.
+fn screenshot_path() -> Result<PathBuf, String> {
+    std::fs::create_dir_all(SCREENSHOTS_DIR).map_err(|e| e.to_string())?;
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?;
+    Ok(PathBuf::from(SCREENSHOTS_DIR).join(format!("screenshot_{}.png", time.as_millis())))
+}
@@ -8,7 +8,7 @@ pub mod world;
 //= IMPORTS ========================================================================================
 
 use crate::game::GameState;
-use crate::world::World;
+use crate::world::{BiomeRegistry, SEA_LEVEL, World};
 
 use voxel_config::Config;
 use voxel_render::Renderer;
@@ -18,6 +18,7 @@ use voxel_winput::{
     window::{Event, Window},
 };
 
+use glam::IVec3;
 use log::LevelFilter;
 
 use std::process;
@@ -25,6 +26,7 @@ use std::process;
 //= CONSTS =========================================================================================
 
 const CONFIG_FILEPATH: &str = "config.json";
+const BIOMES_FILEPATH: &str = "biomes.json";
 
 //= MAIN ===========================================================================================
 
@@ -41,14 +43,22 @@ fn main() -> process::ExitCode {
 
     let mut window = create_window(&config);
 
-    let mut world = World::new(Renderer::max_buffer_sizes());
+    let mut world =
+        World::new(Renderer::max_buffer_sizes(), config.world_depth).unwrap_or_else(|e| {
+            handle_error_and_panic(format!("{e:?}: world_depth in {CONFIG_FILEPATH}"))
+        });
+    world.min = IVec3::from_array(config.world_origin);
+    if !(world.min().y..world.max().y).contains(&SEA_LEVEL) {
+        handle_error_and_panic(format!(
+            "The sea level, {SEA_LEVEL}, must be inside the world: world_origin in {CONFIG_FILEPATH}"
+        ));
+    }
 
     // Create the renderer with some bundles to draw.
     let mut renderer = create_renderer(&config, &window, &world);
 
-    let mut game_state = GameState::new(&mut world, &renderer);
-
-    let _num_cpus = num_cpus::get() as u16;
+    let biomes = BiomeRegistry::load_or_default(BIOMES_FILEPATH);
+    let mut game_state = GameState::new(&mut world, &renderer, biomes, &config);
 
     loop {
         //- Window Inputs and Events Acquisition ---------------------------------------------------
@@ -76,7 +86,7 @@ fn main() -> process::ExitCode {
 
         if !window.is_minimized() {
             profiling::scope!("Renderer Update");
-            if update_rs.world_changed || update_rs.player_moved {
+            if update_rs.world_changed {
                 renderer.reset_frame_counter()
             }
             renderer
@@ -134,11 +144,15 @@ fn configure_input_mapping() -> InputMapping {
     input_mapping.set_primary(InputKind::SlowPace, InputSource::Key { source: KeyCode::ShiftLeft });
     input_mapping.set_secondary(InputKind::SlowPace, InputSource::Key { source: KeyCode::ShiftRight });
     input_mapping.set_primary(InputKind::Flying, InputSource::Key { source: KeyCode::KeyZ });
+    input_mapping.set_primary(InputKind::Undo, InputSource::Key { source: KeyCode::KeyU });
+    input_mapping.set_primary(InputKind::Redo, InputSource::Key { source: KeyCode::KeyR });
+    input_mapping.set_primary(InputKind::WorldStats, InputSource::Key { source: KeyCode::F3 });
+    input_mapping.set_primary(InputKind::Screenshot, InputSource::Key { source: KeyCode::F2 });
 
     input_mapping
 }
 
-fn create_renderer(_config: &Config, window: &Window, world: &World) -> Renderer {
+fn create_renderer(config: &Config, window: &Window, world: &World) -> Renderer {
     let surface_size = window.inner_size();
     let result = Renderer::new(
         window.raw_display_handle(),
@@ -149,7 +163,9 @@ fn create_renderer(_config: &Config, window: &Window, world: &World) -> Renderer
         1,
     );
 
-    result.unwrap_or_else(|e| handle_error_and_panic(e))
+    let mut renderer = result.unwrap_or_else(|e| handle_error_and_panic(e));
+    renderer.set_denoiser_iterations(config.denoiser_iterations);
+    renderer
 }
 
 #[track_caller]
@@ -2,10 +2,10 @@
 //! 2014 OpenSimplex Noise in Java.
 //! by Kurt Spencer
 //!
-//! Ported to Rust by Mason Feurer (Excluding 4D) (Added `NoiseMap` and `MultiNoiseMap`).
+//! Ported to Rust by Mason Feurer (Excluding 4D) (Added `NoiseMap`, `NoiseMap3d` and `MultiNoiseMap`).
 //! I have no idea how this thing works, just translated the control flow.
 //!
-use glam::Vec2;
+use glam::{Vec2, Vec3};
 
 #[derive(Clone)]
 pub(crate) struct NoiseMap {
@@ -31,8 +31,35 @@ impl NoiseMap {
     }
 }
 
+#[derive(Clone)]
+pub(crate) struct NoiseMap3d {
+    noise: Box<OpenSimplexNoise>,
+    scale: f64,
+    freq: f64,
+}
+
+impl NoiseMap3d {
+    pub(crate) fn new(seed: i64, freq: f64, scale: f64) -> Self {
+        Self {
+            noise: Box::new(OpenSimplexNoise::new(seed)),
+            freq,
+            scale,
+        }
+    }
+    pub(crate) fn get(&self, pos: Vec3) -> f32 {
+        let val = self.noise.eval3d(
+            pos.x as f64 * self.freq,
+            pos.y as f64 * self.freq,
+            pos.z as f64 * self.freq,
+        );
+        (((val + 1.0) * 0.5) * self.scale) as f32
+    }
+}
+
 const STRETCH_CONSTANT_2D: f64 = -0.211324865405187; // (1/(2+1).sqrt()-1)/2;
 const SQUISH_CONSTANT_2D: f64 = 0.366025403784439; // ((2+1).sqrt()-1)/2;
+const STRETCH_CONSTANT_3D: f64 = -1.0 / 6.0; // (1/(3+1).sqrt()-1)/3;
+const SQUISH_CONSTANT_3D: f64 = 1.0 / 3.0; // ((3+1).sqrt()-1)/3;
 
 const PSIZE: usize = 2048;
 const PMASK: usize = 2047;
@@ -41,12 +68,14 @@ const PMASK: usize = 2047;
 pub(crate) struct OpenSimplexNoise {
     perm: [usize; PSIZE],
     perm_grad2: [Grad2; PSIZE],
+    perm_grad3: [Grad3; PSIZE],
 }
 
 impl OpenSimplexNoise {
     pub(crate) fn new(mut seed: i64) -> Self {
         let mut perm = [0; PSIZE];
         let mut perm_grad2 = [Grad2::ZERO; PSIZE];
+        let mut perm_grad3 = [Grad3::ZERO; PSIZE];
         let mut source: [usize; PSIZE] = std::array::from_fn(|i| i);
         #[allow(unsafe_code)]
         for i in (0..PSIZE).rev() {
@@ -57,9 +86,14 @@ impl OpenSimplexNoise {
             let r = if r < 0 { r + i as isize + 1 } else { r } as usize;
             perm[i] = source[r];
             perm_grad2[i] = unsafe { GRADIENTS_2D[perm[i]] };
+            perm_grad3[i] = unsafe { GRADIENTS_3D[perm[i]] };
             source[r] = source[i];
         }
-        Self { perm, perm_grad2 }
+        Self {
+            perm,
+            perm_grad2,
+            perm_grad3,
+        }
     }
 
     /// 2D OpenSimplex Noise
@@ -183,6 +217,513 @@ impl OpenSimplexNoise {
         let grad = &self.perm_grad2[self.perm[xsb as usize & PMASK] ^ (ysb as usize & PMASK)];
         grad.dx * dx + grad.dy * dy
     }
+
+    /// 3D OpenSimplex Noise
+    pub(crate) fn eval3d(&self, x: f64, y: f64, z: f64) -> f64 {
+        // Place input coordinates on simplectic honeycomb.
+        let stretch_offset = (x + y + z) * STRETCH_CONSTANT_3D;
+        let xs = x + stretch_offset;
+        let ys = y + stretch_offset;
+        let zs = z + stretch_offset;
+
+        // Floor to get simplectic honeycomb coordinates of rhombohedron (stretched cube) super-cell origin.
+        let xsb: i32 = xs.floor() as i32;
+        let ysb: i32 = ys.floor() as i32;
+        let zsb: i32 = zs.floor() as i32;
+
+        // Compute simplectic honeycomb coordinates relative to rhombohedral origin.
+        let xins: f64 = xs - xsb as f64;
+        let yins: f64 = ys - ysb as f64;
+        let zins: f64 = zs - zsb as f64;
+
+        // Sum those together to get a value that determines which region we're in.
+        let in_sum: f64 = xins + yins + zins;
+
+        // Positions relative to origin point.
+        let squish_offset_ins: f64 = in_sum * SQUISH_CONSTANT_3D;
+        let dx0: f64 = xins + squish_offset_ins;
+        let dy0: f64 = yins + squish_offset_ins;
+        let dz0: f64 = zins + squish_offset_ins;
+
+        // We'll be defining these inside the next block and using them afterwards.
+        let (dx_ext0, mut dy_ext0, dz_ext0): (f64, f64, f64);
+        let (mut dx_ext1, mut dy_ext1, mut dz_ext1): (f64, f64, f64);
+        let (xsv_ext0, mut ysv_ext0, zsv_ext0): (i32, i32, i32);
+        let (mut xsv_ext1, mut ysv_ext1, mut zsv_ext1): (i32, i32, i32);
+
+        let mut value: f64 = 0.0;
+
+        if in_sum <= 1.0 {
+            // We're inside the tetrahedron (3-Simplex) at (0,0,0)
+
+            // Determine which two of (0,0,1), (0,1,0), (1,0,0) are closest.
+            let mut a_point: u8 = 0x01;
+            let mut a_score: f64 = xins;
+            let mut b_point: u8 = 0x02;
+            let mut b_score: f64 = yins;
+            if a_score >= b_score && zins > b_score {
+                b_score = zins;
+                b_point = 0x04;
+            } else if a_score < b_score && zins > a_score {
+                a_score = zins;
+                a_point = 0x04;
+            }
+
+            // Now we determine the two lattice points not part of the tetrahedron that may contribute.
+            // This depends on the closest two tetrahedral vertices, including (0,0,0)
+            let wins: f64 = 1.0 - in_sum;
+            if wins > a_score || wins > b_score {
+                // (0,0,0) is one of the closest two tetrahedral vertices.
+                // Our other closest vertex is the closest out of a and b.
+                let c = if b_score > a_score { b_point } else { a_point };
+
+                if c & 0x01 == 0 {
+                    xsv_ext0 = xsb - 1;
+                    xsv_ext1 = xsb;
+                    dx_ext0 = dx0 + 1.0;
+                    dx_ext1 = dx0;
+                } else {
+                    xsv_ext0 = xsb + 1;
+                    xsv_ext1 = xsb + 1;
+                    dx_ext0 = dx0 - 1.0;
+                    dx_ext1 = dx0 - 1.0;
+                }
+
+                if c & 0x02 == 0 {
+                    ysv_ext0 = ysb;
+                    ysv_ext1 = ysb;
+                    dy_ext0 = dy0;
+                    dy_ext1 = dy0;
+                    if c & 0x01 == 0 {
+                        ysv_ext1 -= 1;
+                        dy_ext1 += 1.0;
+                    } else {
+                        ysv_ext0 -= 1;
+                        dy_ext0 += 1.0;
+                    }
+                } else {
+                    ysv_ext0 = ysb + 1;
+                    ysv_ext1 = ysb + 1;
+                    dy_ext0 = dy0 - 1.0;
+                    dy_ext1 = dy0 - 1.0;
+                }
+
+                if c & 0x04 == 0 {
+                    zsv_ext0 = zsb;
+                    zsv_ext1 = zsb - 1;
+                    dz_ext0 = dz0;
+                    dz_ext1 = dz0 + 1.0;
+                } else {
+                    zsv_ext0 = zsb + 1;
+                    zsv_ext1 = zsb + 1;
+                    dz_ext0 = dz0 - 1.0;
+                    dz_ext1 = dz0 - 1.0;
+                }
+            } else {
+                // (0,0,0) is not one of the closest two tetrahedral vertices.
+                // Our two extra vertices are determined by the closest two.
+                let c = a_point | b_point;
+
+                if c & 0x01 == 0 {
+                    xsv_ext0 = xsb;
+                    xsv_ext1 = xsb - 1;
+                    dx_ext0 = dx0 - 2.0 * SQUISH_CONSTANT_3D;
+                    dx_ext1 = dx0 + 1.0 - SQUISH_CONSTANT_3D;
+                } else {
+                    xsv_ext0 = xsb + 1;
+                    xsv_ext1 = xsb + 1;
+                    dx_ext0 = dx0 - 1.0 - 2.0 * SQUISH_CONSTANT_3D;
+                    dx_ext1 = dx0 - 1.0 - SQUISH_CONSTANT_3D;
+                }
+
+                if c & 0x02 == 0 {
+                    ysv_ext0 = ysb;
+                    ysv_ext1 = ysb - 1;
+                    dy_ext0 = dy0 - 2.0 * SQUISH_CONSTANT_3D;
+                    dy_ext1 = dy0 + 1.0 - SQUISH_CONSTANT_3D;
+                } else {
+                    ysv_ext0 = ysb + 1;
+                    ysv_ext1 = ysb + 1;
+                    dy_ext0 = dy0 - 1.0 - 2.0 * SQUISH_CONSTANT_3D;
+                    dy_ext1 = dy0 - 1.0 - SQUISH_CONSTANT_3D;
+                }
+
+                if c & 0x04 == 0 {
+                    zsv_ext0 = zsb;
+                    zsv_ext1 = zsb - 1;
+                    dz_ext0 = dz0 - 2.0 * SQUISH_CONSTANT_3D;
+                    dz_ext1 = dz0 + 1.0 - SQUISH_CONSTANT_3D;
+                } else {
+                    zsv_ext0 = zsb + 1;
+                    zsv_ext1 = zsb + 1;
+                    dz_ext0 = dz0 - 1.0 - 2.0 * SQUISH_CONSTANT_3D;
+                    dz_ext1 = dz0 - 1.0 - SQUISH_CONSTANT_3D;
+                }
+            }
+
+            // Contribution (0,0,0)
+            value += self.contribution3d(xsb, ysb, zsb, dx0, dy0, dz0);
+
+            // Contribution (1,0,0)
+            let dx1: f64 = dx0 - 1.0 - SQUISH_CONSTANT_3D;
+            let dy1: f64 = dy0 - 0.0 - SQUISH_CONSTANT_3D;
+            let dz1: f64 = dz0 - 0.0 - SQUISH_CONSTANT_3D;
+            value += self.contribution3d(xsb + 1, ysb, zsb, dx1, dy1, dz1);
+
+            // Contribution (0,1,0)
+            let dx2: f64 = dx0 - 0.0 - SQUISH_CONSTANT_3D;
+            let dy2: f64 = dy0 - 1.0 - SQUISH_CONSTANT_3D;
+            let dz2: f64 = dz1;
+            value += self.contribution3d(xsb, ysb + 1, zsb, dx2, dy2, dz2);
+
+            // Contribution (0,0,1)
+            let dx3: f64 = dx2;
+            let dy3: f64 = dy1;
+            let dz3: f64 = dz0 - 1.0 - SQUISH_CONSTANT_3D;
+            value += self.contribution3d(xsb, ysb, zsb + 1, dx3, dy3, dz3);
+        } else if in_sum >= 2.0 {
+            // We're inside the tetrahedron (3-Simplex) at (1,1,1)
+
+            // Determine which two tetrahedral vertices are the closest, out of (1,1,0), (1,0,1), (0,1,1) but not (1,1,1).
+            let mut a_point: u8 = 0x06;
+            let mut a_score: f64 = xins;
+            let mut b_point: u8 = 0x05;
+            let mut b_score: f64 = yins;
+            if a_score <= b_score && zins < b_score {
+                b_score = zins;
+                b_point = 0x03;
+            } else if a_score > b_score && zins < a_score {
+                a_score = zins;
+                a_point = 0x03;
+            }
+
+            // Now we determine the two lattice points not part of the tetrahedron that may contribute.
+            // This depends on the closest two tetrahedral vertices, including (1,1,1)
+            let wins: f64 = 3.0 - in_sum;
+            if wins < a_score || wins < b_score {
+                // (1,1,1) is one of the closest two tetrahedral vertices.
+                // Our other closest vertex is the closest out of a and b.
+                let c = if b_score < a_score { b_point } else { a_point };
+
+                if c & 0x01 != 0 {
+                    xsv_ext0 = xsb + 2;
+                    xsv_ext1 = xsb + 1;
+                    dx_ext0 = dx0 - 2.0 - 3.0 * SQUISH_CONSTANT_3D;
+                    dx_ext1 = dx0 - 1.0 - 3.0 * SQUISH_CONSTANT_3D;
+                } else {
+                    xsv_ext0 = xsb;
+                    xsv_ext1 = xsb;
+                    dx_ext0 = dx0 - 3.0 * SQUISH_CONSTANT_3D;
+                    dx_ext1 = dx0 - 3.0 * SQUISH_CONSTANT_3D;
+                }
+
+                if c & 0x02 != 0 {
+                    ysv_ext0 = ysb + 1;
+                    ysv_ext1 = ysb + 1;
+                    dy_ext0 = dy0 - 1.0 - 3.0 * SQUISH_CONSTANT_3D;
+                    dy_ext1 = dy0 - 1.0 - 3.0 * SQUISH_CONSTANT_3D;
+                    if c & 0x01 != 0 {
+                        ysv_ext1 += 1;
+                        dy_ext1 -= 1.0;
+                    } else {
+                        ysv_ext0 += 1;
+                        dy_ext0 -= 1.0;
+                    }
+                } else {
+                    ysv_ext0 = ysb;
+                    ysv_ext1 = ysb;
+                    dy_ext0 = dy0 - 3.0 * SQUISH_CONSTANT_3D;
+                    dy_ext1 = dy0 - 3.0 * SQUISH_CONSTANT_3D;
+                }
+
+                if c & 0x04 != 0 {
+                    zsv_ext0 = zsb + 1;
+                    zsv_ext1 = zsb + 2;
+                    dz_ext0 = dz0 - 1.0 - 3.0 * SQUISH_CONSTANT_3D;
+                    dz_ext1 = dz0 - 2.0 - 3.0 * SQUISH_CONSTANT_3D;
+                } else {
+                    zsv_ext0 = zsb;
+                    zsv_ext1 = zsb;
+                    dz_ext0 = dz0 - 3.0 * SQUISH_CONSTANT_3D;
+                    dz_ext1 = dz0 - 3.0 * SQUISH_CONSTANT_3D;
+                }
+            } else {
+                // (1,1,1) is not one of the closest two tetrahedral vertices.
+                // Our two extra vertices are determined by the closest two.
+                let c = a_point & b_point;
+
+                if c & 0x01 != 0 {
+                    xsv_ext0 = xsb + 1;
+                    xsv_ext1 = xsb + 2;
+                    dx_ext0 = dx0 - 1.0 - SQUISH_CONSTANT_3D;
+                    dx_ext1 = dx0 - 2.0 - 2.0 * SQUISH_CONSTANT_3D;
+                } else {
+                    xsv_ext0 = xsb;
+                    xsv_ext1 = xsb;
+                    dx_ext0 = dx0 - SQUISH_CONSTANT_3D;
+                    dx_ext1 = dx0 - 2.0 * SQUISH_CONSTANT_3D;
+                }
+
+                if c & 0x02 != 0 {
+                    ysv_ext0 = ysb + 1;
+                    ysv_ext1 = ysb + 2;
+                    dy_ext0 = dy0 - 1.0 - SQUISH_CONSTANT_3D;
+                    dy_ext1 = dy0 - 2.0 - 2.0 * SQUISH_CONSTANT_3D;
+                } else {
+                    ysv_ext0 = ysb;
+                    ysv_ext1 = ysb;
+                    dy_ext0 = dy0 - SQUISH_CONSTANT_3D;
+                    dy_ext1 = dy0 - 2.0 * SQUISH_CONSTANT_3D;
+                }
+
+                if c & 0x04 != 0 {
+                    zsv_ext0 = zsb + 1;
+                    zsv_ext1 = zsb + 2;
+                    dz_ext0 = dz0 - 1.0 - SQUISH_CONSTANT_3D;
+                    dz_ext1 = dz0 - 2.0 - 2.0 * SQUISH_CONSTANT_3D;
+                } else {
+                    zsv_ext0 = zsb;
+                    zsv_ext1 = zsb;
+                    dz_ext0 = dz0 - SQUISH_CONSTANT_3D;
+                    dz_ext1 = dz0 - 2.0 * SQUISH_CONSTANT_3D;
+                }
+            }
+
+            // Contribution (1,1,0)
+            let dx3: f64 = dx0 - 1.0 - 2.0 * SQUISH_CONSTANT_3D;
+            let dy3: f64 = dy0 - 1.0 - 2.0 * SQUISH_CONSTANT_3D;
+            let dz3: f64 = dz0 - 0.0 - 2.0 * SQUISH_CONSTANT_3D;
+            value += self.contribution3d(xsb + 1, ysb + 1, zsb, dx3, dy3, dz3);
+
+            // Contribution (1,0,1)
+            let dx2: f64 = dx3;
+            let dy2: f64 = dy0 - 0.0 - 2.0 * SQUISH_CONSTANT_3D;
+            let dz2: f64 = dz0 - 1.0 - 2.0 * SQUISH_CONSTANT_3D;
+            value += self.contribution3d(xsb + 1, ysb, zsb + 1, dx2, dy2, dz2);
+
+            // Contribution (0,1,1)
+            let dx1: f64 = dx0 - 0.0 - 2.0 * SQUISH_CONSTANT_3D;
+            let dy1: f64 = dy3;
+            let dz1: f64 = dz2;
+            value += self.contribution3d(xsb, ysb + 1, zsb + 1, dx1, dy1, dz1);
+
+            // Contribution (1,1,1)
+            let dx0: f64 = dx0 - 1.0 - 3.0 * SQUISH_CONSTANT_3D;
+            let dy0: f64 = dy0 - 1.0 - 3.0 * SQUISH_CONSTANT_3D;
+            let dz0: f64 = dz0 - 1.0 - 3.0 * SQUISH_CONSTANT_3D;
+            value += self.contribution3d(xsb + 1, ysb + 1, zsb + 1, dx0, dy0, dz0);
+        } else {
+            // We're inside the octahedron (Rectified 3-Simplex) in between.
+            let a_score: f64;
+            let mut a_point: u8;
+            let mut a_is_further_side: bool;
+            let b_score: f64;
+            let mut b_point: u8;
+            let mut b_is_further_side: bool;
+
+            // Decide between point (0,0,1) and (1,1,0) as closest
+            let p1: f64 = xins + yins;
+            if p1 > 1.0 {
+                a_score = p1 - 1.0;
+                a_point = 0x03;
+                a_is_further_side = true;
+            } else {
+                a_score = 1.0 - p1;
+                a_point = 0x04;
+                a_is_further_side = false;
+            }
+
+            // Decide between point (0,1,0) and (1,0,1) as closest
+            let p2: f64 = xins + zins;
+            if p2 > 1.0 {
+                b_score = p2 - 1.0;
+                b_point = 0x05;
+                b_is_further_side = true;
+            } else {
+                b_score = 1.0 - p2;
+                b_point = 0x02;
+                b_is_further_side = false;
+            }
+
+            // The closest out of the two (1,0,0) and (0,1,1) will replace the furthest out of the two decided above, if closer.
+            let p3: f64 = yins + zins;
+            let (score, point, is_further_side) = if p3 > 1.0 {
+                (p3 - 1.0, 0x06, true)
+            } else {
+                (1.0 - p3, 0x01, false)
+            };
+            if a_score <= b_score && a_score < score {
+                a_point = point;
+                a_is_further_side = is_further_side;
+            } else if a_score > b_score && b_score < score {
+                b_point = point;
+                b_is_further_side = is_further_side;
+            }
+
+            // Where each of the two closest points are determines how the extra two vertices are calculated.
+            if a_is_further_side == b_is_further_side {
+                if a_is_further_side {
+                    // Both closest points on (1,1,1) side
+
+                    // One of the two extra points is (1,1,1)
+                    dx_ext0 = dx0 - 1.0 - 3.0 * SQUISH_CONSTANT_3D;
+                    dy_ext0 = dy0 - 1.0 - 3.0 * SQUISH_CONSTANT_3D;
+                    dz_ext0 = dz0 - 1.0 - 3.0 * SQUISH_CONSTANT_3D;
+                    xsv_ext0 = xsb + 1;
+                    ysv_ext0 = ysb + 1;
+                    zsv_ext0 = zsb + 1;
+
+                    // Other extra point is based on the shared axis.
+                    let c = a_point & b_point;
+                    dx_ext1 = dx0 - 2.0 * SQUISH_CONSTANT_3D;
+                    dy_ext1 = dy0 - 2.0 * SQUISH_CONSTANT_3D;
+                    dz_ext1 = dz0 - 2.0 * SQUISH_CONSTANT_3D;
+                    xsv_ext1 = xsb;
+                    ysv_ext1 = ysb;
+                    zsv_ext1 = zsb;
+                    if c & 0x01 != 0 {
+                        dx_ext1 -= 2.0;
+                        xsv_ext1 += 2;
+                    } else if c & 0x02 != 0 {
+                        dy_ext1 -= 2.0;
+                        ysv_ext1 += 2;
+                    } else {
+                        dz_ext1 -= 2.0;
+                        zsv_ext1 += 2;
+                    }
+                } else {
+                    // Both closest points on (0,0,0) side
+
+                    // One of the two extra points is (0,0,0)
+                    dx_ext0 = dx0;
+                    dy_ext0 = dy0;
+                    dz_ext0 = dz0;
+                    xsv_ext0 = xsb;
+                    ysv_ext0 = ysb;
+                    zsv_ext0 = zsb;
+
+                    // Other extra point is based on the omitted axis.
+                    let c = a_point | b_point;
+                    (dx_ext1, dy_ext1, dz_ext1, xsv_ext1, ysv_ext1, zsv_ext1) =
+                        Self::permutation_of_one_one_minus_one(c, xsb, ysb, zsb, dx0, dy0, dz0);
+                }
+            } else {
+                // One point on (0,0,0) side, one point on (1,1,1) side
+                let (c1, c2) = if a_is_further_side {
+                    (a_point, b_point)
+                } else {
+                    (b_point, a_point)
+                };
+
+                // One contribution is a permutation of (1,1,-1)
+                (dx_ext0, dy_ext0, dz_ext0, xsv_ext0, ysv_ext0, zsv_ext0) =
+                    Self::permutation_of_one_one_minus_one(c1, xsb, ysb, zsb, dx0, dy0, dz0);
+
+                // One contribution is a permutation of (0,0,2)
+                dx_ext1 = dx0 - 2.0 * SQUISH_CONSTANT_3D;
+                dy_ext1 = dy0 - 2.0 * SQUISH_CONSTANT_3D;
+                dz_ext1 = dz0 - 2.0 * SQUISH_CONSTANT_3D;
+                xsv_ext1 = xsb;
+                ysv_ext1 = ysb;
+                zsv_ext1 = zsb;
+                if c2 & 0x01 != 0 {
+                    dx_ext1 -= 2.0;
+                    xsv_ext1 += 2;
+                } else if c2 & 0x02 != 0 {
+                    dy_ext1 -= 2.0;
+                    ysv_ext1 += 2;
+                } else {
+                    dz_ext1 -= 2.0;
+                    zsv_ext1 += 2;
+                }
+            }
+
+            // Contribution (1,0,0)
+            let dx1: f64 = dx0 - 1.0 - SQUISH_CONSTANT_3D;
+            let dy1: f64 = dy0 - 0.0 - SQUISH_CONSTANT_3D;
+            let dz1: f64 = dz0 - 0.0 - SQUISH_CONSTANT_3D;
+            value += self.contribution3d(xsb + 1, ysb, zsb, dx1, dy1, dz1);
+
+            // Contribution (0,1,0)
+            let dx2: f64 = dx0 - 0.0 - SQUISH_CONSTANT_3D;
+            let dy2: f64 = dy0 - 1.0 - SQUISH_CONSTANT_3D;
+            let dz2: f64 = dz1;
+            value += self.contribution3d(xsb, ysb + 1, zsb, dx2, dy2, dz2);
+
+            // Contribution (0,0,1)
+            let dx3: f64 = dx2;
+            let dy3: f64 = dy1;
+            let dz3: f64 = dz0 - 1.0 - SQUISH_CONSTANT_3D;
+            value += self.contribution3d(xsb, ysb, zsb + 1, dx3, dy3, dz3);
+
+            // Contribution (1,1,0)
+            let dx4: f64 = dx0 - 1.0 - 2.0 * SQUISH_CONSTANT_3D;
+            let dy4: f64 = dy0 - 1.0 - 2.0 * SQUISH_CONSTANT_3D;
+            let dz4: f64 = dz0 - 0.0 - 2.0 * SQUISH_CONSTANT_3D;
+            value += self.contribution3d(xsb + 1, ysb + 1, zsb, dx4, dy4, dz4);
+
+            // Contribution (1,0,1)
+            let dx5: f64 = dx4;
+            let dy5: f64 = dy0 - 0.0 - 2.0 * SQUISH_CONSTANT_3D;
+            let dz5: f64 = dz0 - 1.0 - 2.0 * SQUISH_CONSTANT_3D;
+            value += self.contribution3d(xsb + 1, ysb, zsb + 1, dx5, dy5, dz5);
+
+            // Contribution (0,1,1)
+            let dx6: f64 = dx0 - 0.0 - 2.0 * SQUISH_CONSTANT_3D;
+            let dy6: f64 = dy4;
+            let dz6: f64 = dz5;
+            value += self.contribution3d(xsb, ysb + 1, zsb + 1, dx6, dy6, dz6);
+        }
+
+        // First extra vertex
+        value += self.contribution3d(xsv_ext0, ysv_ext0, zsv_ext0, dx_ext0, dy_ext0, dz_ext0);
+
+        // Second extra vertex
+        value += self.contribution3d(xsv_ext1, ysv_ext1, zsv_ext1, dx_ext1, dy_ext1, dz_ext1);
+
+        value
+    }
+
+    /// The extra vertex of the octahedron that is a permutation of (1,1,-1),
+    /// the -1 goes on the first axis not set in `c`.
+    #[allow(clippy::too_many_arguments)]
+    fn permutation_of_one_one_minus_one(
+        c: u8,
+        xsb: i32,
+        ysb: i32,
+        zsb: i32,
+        dx0: f64,
+        dy0: f64,
+        dz0: f64,
+    ) -> (f64, f64, f64, i32, i32, i32) {
+        let dx = dx0 - 1.0 - SQUISH_CONSTANT_3D;
+        let dy = dy0 - 1.0 - SQUISH_CONSTANT_3D;
+        let dz = dz0 - 1.0 - SQUISH_CONSTANT_3D;
+        if c & 0x01 == 0 {
+            (dx + 2.0, dy, dz, xsb - 1, ysb + 1, zsb + 1)
+        } else if c & 0x02 == 0 {
+            (dx, dy + 2.0, dz, xsb + 1, ysb - 1, zsb + 1)
+        } else {
+            (dx, dy, dz + 2.0, xsb + 1, ysb + 1, zsb - 1)
+        }
+    }
+
+    fn contribution3d(&self, xsb: i32, ysb: i32, zsb: i32, dx: f64, dy: f64, dz: f64) -> f64 {
+        let mut attn: f64 = 2.0 - dx * dx - dy * dy - dz * dz;
+        if attn > 0.0 {
+            attn *= attn;
+            attn * attn * self.extrapolate3d(xsb, ysb, zsb, dx, dy, dz)
+        } else {
+            0.0
+        }
+    }
+
+    fn extrapolate3d(&self, xsb: i32, ysb: i32, zsb: i32, dx: f64, dy: f64, dz: f64) -> f64 {
+        let grad = &self.perm_grad3[self.perm
+            [self.perm[xsb as usize & PMASK] ^ (ysb as usize & PMASK)]
+            ^ (zsb as usize & PMASK)];
+        grad.dx * dx + grad.dy * dy + grad.dz * dz
+    }
 }
 
 #[derive(Clone, Copy)]
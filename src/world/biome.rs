@@ -0,0 +1,272 @@
+//= IMPORTS ========================================================================================
+
+use crate::world::{BRANCH_LEAVES_RADIUS, STRUCTURE_MARGIN, Schematic, TreeGen};
+
+use voxel_config::{BiomesConfig, TreeConfig};
+use voxel_render::Voxel;
+
+use hashbrown::HashMap;
+
+use std::ops::Range;
+use std::sync::Arc;
+
+//= BIOMES =========================================================================================
+
+/// A structure that a vegetation rule can spawn on the surface.
+pub enum Structure {
+    Tree(TreeGen),
+    Cactus,
+    Spruce,
+    /// Pasted ignoring its air, with a random rotation.
+    Schematic {
+        schematic: Arc<Schematic>,
+        offset_y: i32,
+    },
+}
+
+pub struct Vegetation {
+    pub structure: Structure,
+    /// Chance for a column to spawn the structure, scaled by the vegetation noise.
+    pub chance: f32,
+}
+
+pub struct Biome {
+    pub name: String,
+    pub surface: Voxel,
+    /// Layers below the surface from the top, as voxel and thickness.
+    pub layers: Vec<(Voxel, u32)>,
+    pub temperature: Range<f32>,
+    pub moisture: Range<f32>,
+    /// Tried in order, the first rule that succeeds spawns its structure.
+    pub vegetation: Vec<Vegetation>,
+}
+
+impl Biome {
+    /// How many voxels below the surface the stone starts.
+    #[must_use]
+    pub fn depth(&self) -> i32 {
+        self.layers
+            .iter()
+            .map(|(_, thickness)| *thickness as i32)
+            .sum()
+    }
+}
+
+/// The biomes a world is generated with, built from a `BiomesConfig`.
+pub struct BiomeRegistry {
+    /// Climate distance over which two adjacent biomes are dithered into each other.
+    pub blend: f32,
+    biomes: Vec<Biome>,
+}
+
+impl BiomeRegistry {
+    /// Loads the biomes file, falling back to the built-in biomes if it is missing or invalid.
+    #[must_use]
+    pub fn load_or_default(filename: &str) -> Self {
+        Self::from_config(&BiomesConfig::load_or_default(filename)).unwrap_or_else(|e| {
+            log::error!("{e}: {filename}");
+            Self::from_config(&BiomesConfig::default()).unwrap()
+        })
+    }
+
+    /// Resolves the voxel and tree names of the config.
+    pub fn from_config(config: &BiomesConfig) -> Result<Self, String> {
+        if config.biomes.is_empty() {
+            return Err("No biomes defined".into());
+        }
+
+        let schematics = load_schematics(config)?;
+        let mut biomes = Vec::with_capacity(config.biomes.len());
+        for biome in &config.biomes {
+            let name = &biome.name;
+            let voxel = |voxel: &str| {
+                Voxel::from_name(voxel)
+                    .ok_or_else(|| format!("Unknown voxel `{voxel}` in biome `{name}`"))
+            };
+
+            let layers = biome
+                .layers
+                .iter()
+                .map(|layer| {
+                    if layer.thickness == 0 {
+                        return Err(format!(
+                            "Layer of `{}` in biome `{name}` is empty",
+                            layer.voxel
+                        ));
+                    }
+                    Ok((voxel(&layer.voxel)?, layer.thickness))
+                })
+                .collect::<Result<_, String>>()?;
+            for (range, climate) in [
+                (biome.temperature, "temperature"),
+                (biome.moisture, "moisture"),
+            ] {
+                if !(0.0 <= range[0] && range[0] < range[1] && range[1] <= 1.0) {
+                    return Err(format!(
+                        "The {climate} range of biome `{name}` is empty or outside 0 to 1"
+                    ));
+                }
+            }
+
+            let vegetation = biome
+                .vegetation
+                .iter()
+                .map(|rule| {
+                    let structure = structure(config, &schematics, name, &rule.structure)?;
+                    Ok(Vegetation {
+                        structure,
+                        chance: rule.chance,
+                    })
+                })
+                .collect::<Result<_, String>>()?;
+
+            biomes.push(Biome {
+                name: name.clone(),
+                surface: voxel(&biome.surface)?,
+                layers,
+                temperature: biome.temperature[0]..biome.temperature[1],
+                moisture: biome.moisture[0]..biome.moisture[1],
+                vegetation,
+            });
+        }
+
+        Ok(Self {
+            blend: config.blend,
+            biomes,
+        })
+    }
+
+    /// The first biome whose ranges contain the climate, or the last one if none does.
+    #[must_use]
+    pub fn biome_at(&self, temp: f32, moisture: f32) -> &Biome {
+        self.biomes
+            .iter()
+            .find(|biome| biome.temperature.contains(&temp) && biome.moisture.contains(&moisture))
+            .unwrap_or_else(|| self.biomes.last().unwrap())
+    }
+}
+
+fn structure(
+    config: &BiomesConfig,
+    schematics: &HashMap<&str, (Arc<Schematic>, i32)>,
+    biome: &str,
+    name: &str,
+) -> Result<Structure, String> {
+    match name {
+        "cactus" => Ok(Structure::Cactus),
+        "spruce" => Ok(Structure::Spruce),
+        name if schematics.contains_key(name) => {
+            let (schematic, offset_y) = &schematics[name];
+            Ok(Structure::Schematic {
+                schematic: Arc::clone(schematic),
+                offset_y: *offset_y,
+            })
+        }
+        tree => {
+            let tree = config.trees.iter().find(|t| t.name == tree);
+            let tree =
+                tree.ok_or_else(|| format!("Unknown structure `{name}` in biome `{biome}`"))?;
+            Ok(Structure::Tree(tree_gen(tree)?))
+        }
+    }
+}
+
+/// Loads every schematic file once, even if more rules refer to it.
+fn load_schematics(config: &BiomesConfig) -> Result<HashMap<&str, (Arc<Schematic>, i32)>, String> {
+    let mut schematics = HashMap::new();
+    for schematic in &config.schematics {
+        let name = &schematic.name;
+        let loaded = Schematic::load(&schematic.file)
+            .map_err(|e| format!("{e:?} loading schematic `{name}`: {}", schematic.file))?;
+        // Structures can't grow farther than the margin, or the chunks would cut them.
+        let size = loaded.size();
+        if size.x.max(size.z) / 2 > STRUCTURE_MARGIN {
+            return Err(format!(
+                "Schematic `{name}` is wider than {} voxels",
+                STRUCTURE_MARGIN * 2
+            ));
+        }
+        schematics.insert(name.as_str(), (Arc::new(loaded), schematic.offset_y));
+    }
+    Ok(schematics)
+}
+
+fn tree_gen(tree: &TreeConfig) -> Result<TreeGen, String> {
+    let name = &tree.name;
+    let voxel = |voxel: &str| {
+        Voxel::from_name(voxel).ok_or_else(|| format!("Unknown voxel `{voxel}` in tree `{name}`"))
+    };
+    if tree.height[0] >= tree.height[1] || tree.branch_count[0] >= tree.branch_count[1] {
+        return Err(format!(
+            "Empty height or branch count range in tree `{name}`"
+        ));
+    }
+    let [low, high] = tree.branch_height;
+    if !(0.0 <= low && low < high && high <= 1.0) {
+        return Err(format!(
+            "Branch height range of tree `{name}` must be inside 0 to 1"
+        ));
+    }
+    let [short, long] = tree.branch_len;
+    if !(0.0 <= short && short < long) {
+        return Err(format!("Empty branch length range in tree `{name}`"));
+    }
+    // The branches grow sideways, their leaves must stay within the margin of the chunks.
+    if long + BRANCH_LEAVES_RADIUS as f32 > STRUCTURE_MARGIN as f32 {
+        return Err(format!(
+            "Branches of tree `{name}` are longer than {} voxels",
+            STRUCTURE_MARGIN - BRANCH_LEAVES_RADIUS as i32
+        ));
+    }
+
+    Ok(TreeGen {
+        height: tree.height[0]..tree.height[1],
+        bark: voxel(&tree.bark)?,
+        leaves: voxel(&tree.leaves)?,
+        leaves_decay: tree.leaves_decay,
+        branch_count: tree.branch_count[0]..tree.branch_count[1],
+        branch_height: tree.branch_height[0]..tree.branch_height[1],
+        branch_len: tree.branch_len[0]..tree.branch_len[1],
+    })
+}
+
+//= TESTS ==========================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use voxel_config::BiomeConfig;
+
+    fn with_forest(edit: impl FnOnce(&mut BiomeConfig)) -> Result<BiomeRegistry, String> {
+        let mut config = BiomesConfig::default();
+        edit(config.biomes.last_mut().unwrap());
+        BiomeRegistry::from_config(&config)
+    }
+
+    fn with_trees(edit: impl Fn(&mut TreeConfig)) -> Result<BiomeRegistry, String> {
+        let mut config = BiomesConfig::default();
+        config.trees.iter_mut().for_each(edit);
+        BiomeRegistry::from_config(&config)
+    }
+
+    #[test]
+    fn invalid_biomes_are_rejected() {
+        assert!(with_forest(|_| ()).is_ok());
+        assert!(with_forest(|forest| forest.layers[0].thickness = 0).is_err());
+        assert!(with_forest(|forest| forest.temperature = [0.5, 0.5]).is_err());
+        assert!(with_forest(|forest| forest.temperature = [0.6, 0.4]).is_err());
+        assert!(with_forest(|forest| forest.moisture = [-0.1, 0.5]).is_err());
+        assert!(with_forest(|forest| forest.moisture = [0.5, 1.1]).is_err());
+
+        assert!(with_trees(|_| ()).is_ok());
+        assert!(with_trees(|tree| tree.height = [8, 8]).is_err());
+        assert!(with_trees(|tree| tree.branch_count = [3, 2]).is_err());
+        assert!(with_trees(|tree| tree.branch_height = [0.5, 0.5]).is_err());
+        assert!(with_trees(|tree| tree.branch_height = [0.8, 0.5]).is_err());
+        assert!(with_trees(|tree| tree.branch_height = [0.5, 1.5]).is_err());
+        assert!(with_trees(|tree| tree.branch_len = [4.0, 4.0]).is_err());
+        assert!(with_trees(|tree| tree.branch_len = [8.0, 3.0]).is_err());
+        assert!(with_trees(|tree| tree.branch_len = [3.0, 14.0]).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//= IMPORTS ========================================================================================
+
+use crate::world::World;
+
+use voxel_render::Node;
+
+use hashbrown::HashMap;
+
+//= CONSTANTS ======================================================================================
+
+/// Above this ratio of free nodes, inside the uploaded range, it's worth to call `World::compact`.
+pub const MAX_FRAGMENTATION: f32 = 0.5;
+
+//= COMPACTION =====================================================================================
+
+/// Defragment the pool of nodes.
+impl World {
+    /// Rewrites the whole tree depth first at the start of the pool, dropping the holes left
+    /// by freed nodes.
+    ///
+    /// Returns the remapping of the nodes where `remap[new_idx] == old_idx`,
+    /// every node has moved so the whole `World::nodes` is marked as modified.
+    pub fn compact(&mut self) -> Vec<u32> {
+        let (nodes, remap) = self.compacted_nodes();
+        let count = nodes.len();
+        self.dirty.mark(0, self.last_used_node + 1);
+
+        self.nodes[..count].copy_from_slice(&nodes);
+        for node in &mut self.nodes[count..=self.last_used_node as usize] {
+            *node = Node::ZERO;
+        }
+        self.start_search = count as u32;
+        self.last_used_node = count as u32 - 1;
+        self.recount_refs();
+        remap
+    }
+
+    /// The ratio, between 0 and 1, of free nodes inside the range returned by `World::nodes`.
+    pub fn fragmentation(&self) -> f32 {
+        let used = self.nodes().iter().filter(|node| node.is_used()).count();
+        1.0 - used as f32 / (self.last_used_node + 1) as f32
+    }
+
+    /// Returns a copy of the nodes reachable from the root, rewritten depth first
+    /// so that there are no free holes in between, and the index each one came from.
+    ///
+    /// Shared children are rewritten once and stay shared, after every node pointing to them,
+    /// so the children of a node always come after it.
+    pub(crate) fn compacted_nodes(&self) -> (Vec<Node>, Vec<u32>) {
+        // Source first child to the split nodes pointing to it not rewritten yet.
+        let mut parents_left: HashMap<u32, u32> = HashMap::new();
+        let mut stack = vec![0];
+        while let Some(idx) = stack.pop() {
+            let node = self.get_node(idx);
+            if !node.is_split() {
+                continue;
+            }
+            let parents = parents_left.entry(node.first_child()).or_default();
+            *parents += 1;
+            // Shared children are visited only the first time.
+            if *parents == 1 {
+                stack.extend((0..8).map(|i| node.get_child(i)));
+            }
+        }
+
+        let mut nodes = vec![self.get_node(0)];
+        let mut remap = vec![0];
+        // Pairs of (source index, destination index).
+        let mut stack = vec![(0, 0)];
+        // Source first child to the destination nodes waiting for it.
+        let mut waiting: HashMap<u32, Vec<u32>> = HashMap::new();
+
+        while let Some((src, dst)) = stack.pop() {
+            let node = self.get_node(src);
+            if !node.is_split() {
+                continue;
+            }
+            let parents = parents_left.get_mut(&node.first_child()).unwrap();
+            *parents -= 1;
+            if *parents > 0 {
+                waiting.entry(node.first_child()).or_default().push(dst);
+                continue;
+            }
+            let first_child = nodes.len() as u32;
+            for parent in waiting.remove(&node.first_child()).unwrap_or_default() {
+                nodes[parent as usize].set_first_child(first_child);
+            }
+            nodes[dst as usize].set_first_child(first_child);
+            for i in 0..8 {
+                nodes.push(self.get_node(node.get_child(i)));
+                remap.push(node.get_child(i));
+            }
+            for i in (0..8).rev() {
+                stack.push((node.get_child(i), first_child + i));
+            }
+        }
+        (nodes, remap)
+    }
+}
+
+//= TESTS ==========================================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::world::World;
+
+    use voxel_render::Voxel;
+
+    use fastrand::Rng;
+    use glam::{IVec3, ivec3};
+
+    #[test]
+    fn compaction_keeps_the_voxels() {
+        let mut world = World::new_region(IVec3::ZERO, 6);
+        let mut rng = Rng::with_seed(3);
+        for _ in 0..40 {
+            let pos = ivec3(rng.i32(0..64), rng.i32(0..64), rng.i32(0..64));
+            let voxel = Voxel(rng.u16(1..4));
+            world
+                .fill_box(pos, pos + IVec3::splat(rng.i32(0..6)), voxel)
+                .unwrap();
+        }
+        // Clearing whole regions frees the nodes inside them.
+        world
+            .fill_box(IVec3::ZERO, IVec3::splat(31), Voxel::AIR)
+            .unwrap();
+        world
+            .fill_box(ivec3(32, 0, 32), ivec3(63, 63, 63), Voxel::AIR)
+            .unwrap();
+        assert!(world.fragmentation() > 0.0);
+
+        let positions = || (0..64 * 64 * 64).map(|i| ivec3(i % 64, i / 64 % 64, i / 4096));
+        let voxels = positions()
+            .map(|pos| world.get_voxel(pos).unwrap())
+            .collect::<Vec<_>>();
+        let old_nodes = world.nodes().to_vec();
+        let remap = world.compact();
+
+        assert!(world.fragmentation() <= 0.0);
+        for (pos, voxel) in positions().zip(voxels) {
+            assert!(world.get_voxel(pos).unwrap() == voxel, "{pos}");
+        }
+
+        // Every node came from an equal one, pointing to the same children.
+        assert_eq!(remap.len(), world.nodes().len());
+        for (new_idx, &old_idx) in remap.iter().enumerate() {
+            let (new, old) = (world.nodes()[new_idx], old_nodes[old_idx as usize]);
+            assert_eq!(new.is_split(), old.is_split(), "{new_idx}");
+            if !new.is_split() {
+                assert_eq!(new.raw(), old.raw(), "{new_idx}");
+                continue;
+            }
+            for i in 0..8 {
+                let child = new.first_child() + i;
+                assert_eq!(remap[child as usize], old.first_child() + i, "{new_idx}");
+            }
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//= IMPORTS ========================================================================================
+
+use crate::world::World;
+
+use voxel_render::Node;
+
+use hashbrown::HashMap;
+
+//= DEDUPLICATION ==================================================================================
+
+/// Turn the tree into a directed acyclic graph (DAG).
+///
+/// Identical subtrees, like the solid stone below every column, point to the same nodes,
+/// so the shaders walk it like any other tree. The split nodes keep a count of the references
+/// to their children, and the shared ones are copied before being modified, so editing the world
+/// works the same after a deduplication.
+impl World {
+    /// Rewrites the tree at the start of the pool sharing every identical group of 8 sibling
+    /// nodes, dropping the holes left by freed nodes like `World::compact`.
+    ///
+    /// Returns how many nodes were saved, the whole `World::nodes` is marked as modified.
+    pub fn deduplicate(&mut self) -> usize {
+        let used = self.nodes().iter().filter(|node| node.is_used()).count();
+
+        // The root children are left unshared, so the world can scroll by moving them around.
+        let mut dedup = Dedup {
+            nodes: vec![self.get_node(0); 9],
+            unique: HashMap::new(),
+            moved: HashMap::new(),
+        };
+        let root = self.get_node(0);
+        if root.is_split() {
+            for i in 0..8 {
+                dedup.nodes[1 + i as usize] = dedup.node(self, root.get_child(i));
+            }
+            dedup.nodes[0].set_first_child(1);
+        } else {
+            dedup.nodes.truncate(1);
+        }
+
+        let nodes = dedup.nodes;
+        let count = nodes.len();
+        self.dirty.mark(0, self.last_used_node + 1);
+
+        self.nodes[..count].copy_from_slice(&nodes);
+        for node in &mut self.nodes[count..=self.last_used_node as usize] {
+            *node = Node::ZERO;
+        }
+        self.start_search = count as u32;
+        self.last_used_node = count as u32 - 1;
+        self.recount_refs();
+        used.saturating_sub(count)
+    }
+}
+
+struct Dedup {
+    nodes: Vec<Node>,
+    /// The raw children of every group written, to their first child.
+    unique: HashMap<[u32; 8], u32>,
+    /// Source first child to destination first child, so already shared groups
+    /// are visited once.
+    moved: HashMap<u32, u32>,
+}
+
+impl Dedup {
+    /// Returns the node at `idx` of `world`, with its children written deduplicated.
+    fn node(&mut self, world: &World, idx: u32) -> Node {
+        let node = world.get_node(idx);
+        if !node.is_split() {
+            return node;
+        }
+        if let Some(&first_child) = self.moved.get(&node.first_child()) {
+            return Node::new_split(first_child);
+        }
+
+        let children = std::array::from_fn(|i| self.node(world, node.get_child(i as u32)).raw());
+        let first_child = *self.unique.entry(children).or_insert_with(|| {
+            let first_child = self.nodes.len() as u32;
+            self.nodes.extend(children.map(Node::from_raw));
+            first_child
+        });
+        self.moved.insert(node.first_child(), first_child);
+        Node::new_split(first_child)
+    }
+}
+
+//= TESTS ==========================================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::world::{BiomeRegistry, Leaf, World, WorldGen, group};
+
+    use voxel_render::Voxel;
+
+    use fastrand::Rng;
+    use glam::{IVec3, ivec3};
+
+    /// Applies the same random edits to both worlds.
+    fn edit_both(worlds: [&mut World; 2], seed: u64) {
+        for world in worlds {
+            let mut rng = Rng::with_seed(seed);
+            for _ in 0..30 {
+                let pos = ivec3(rng.i32(0..128), rng.i32(0..128), rng.i32(0..128));
+                let voxel = Voxel(rng.u16(0..4));
+                match rng.u8(0..3) {
+                    0 => drop(world.set_voxel(pos, voxel).unwrap()),
+                    1 => drop(world.fill_box(pos, pos + rng.i32(1..24), voxel).unwrap()),
+                    _ => drop(
+                        world
+                            .sphere(pos, rng.u32(1..12), voxel, 0.0, &mut rng)
+                            .unwrap(),
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Every group reachable from the root is counted as many times as the nodes pointing to it.
+    fn assert_refs_match_tree(world: &World) {
+        let mut recounted = world.clone();
+        recounted.recount_refs();
+        let mut stack = vec![0];
+        while let Some(idx) = stack.pop() {
+            let node = world.get_node(idx);
+            if !node.is_split() {
+                continue;
+            }
+            let group = group(node.first_child());
+            assert!(
+                world.refs[group] > 0,
+                "reachable group {group} without references"
+            );
+            assert_eq!(world.refs[group], recounted.refs[group], "group {group}");
+            stack.extend((0..8).map(|i| node.get_child(i)));
+        }
+    }
+
+    fn leaves(world: &World) -> Vec<Leaf> {
+        world.leaves().collect()
+    }
+
+    #[test]
+    fn edits_on_shared_subtrees_match_a_plain_tree() {
+        crate::open_simplex::init_gradients();
+        let biomes = BiomeRegistry::from_config(&Default::default()).unwrap();
+        let mut generated = World::new_region(IVec3::ZERO, 7);
+        WorldGen::new(3, biomes)
+            .populate(generated.min(), generated.max(), &mut generated)
+            .unwrap();
+
+        for seed in 0..20 {
+            let mut plain = generated.clone();
+            let mut shared = generated.clone();
+            assert!(shared.deduplicate() > 0);
+            assert_refs_match_tree(&shared);
+
+            edit_both([&mut plain, &mut shared], seed);
+            assert!(leaves(&plain) == leaves(&shared), "seed {seed}");
+            assert_refs_match_tree(&shared);
+
+            plain.compact();
+            shared.compact();
+            assert!(
+                leaves(&plain) == leaves(&shared),
+                "seed {seed} after compact"
+            );
+            assert_refs_match_tree(&shared);
+
+            edit_both([&mut plain, &mut shared], seed + 100);
+            assert!(
+                leaves(&plain) == leaves(&shared),
+                "seed {seed} after compact"
+            );
+            assert_refs_match_tree(&shared);
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//= IMPORTS ========================================================================================
+
+use crate::world::{NodeSeq, World, WorldErr};
+
+use voxel_render::{Node, Voxel};
+
+use glam::{IVec3, Vec3, ivec3};
+
+//= COVERAGE =======================================================================================
+
+/// How much of a node is covered by the shape passed to `World::fill_shape`.
+///
+/// Variants are ordered so that the `min` of two coverages is the coverage of the intersection.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Coverage {
+    Outside,
+    Partial,
+    Inside,
+}
+
+impl Coverage {
+    /// Coverage of the node starting at `node_min` with the given `size`,
+    /// by the box going from `min` to `max` inclusive.
+    #[must_use]
+    pub fn of_box(node_min: IVec3, size: u32, min: IVec3, max: IVec3) -> Self {
+        let node_max = node_min + IVec3::splat(size as i32 - 1);
+        if node_max.cmplt(min).any() || node_min.cmpgt(max).any() {
+            Self::Outside
+        } else if node_min.cmpge(min).all() && node_max.cmple(max).all() {
+            Self::Inside
+        } else {
+            Self::Partial
+        }
+    }
+
+    /// Coverage of the node starting at `node_min` with the given `size`,
+    /// by the voxels whose center is nearer than `r` to the center of the `pos` voxel.
+    #[must_use]
+    pub fn of_sphere(node_min: IVec3, size: u32, pos: IVec3, r: u32) -> Self {
+        let pos_center = pos.as_vec3() + Vec3::splat(0.5);
+        let first_center = node_min.as_vec3() + Vec3::splat(0.5);
+        let last_center = first_center + Vec3::splat(size as f32 - 1.0);
+        let r_sq = r as f32 * r as f32;
+
+        let nearest = pos_center.clamp(first_center, last_center);
+        let farthest = Vec3::select(
+            (pos_center - first_center).cmpgt(last_center - pos_center),
+            first_center,
+            last_center,
+        );
+
+        if (nearest - pos_center).length_squared() >= r_sq {
+            Self::Outside
+        } else if (farthest - pos_center).length_squared() < r_sq {
+            Self::Inside
+        } else {
+            Self::Partial
+        }
+    }
+
+    /// Like `Coverage::of_sphere`, but each voxel has a `decay` chance to be left out,
+    /// decided by hashing its position with `salt`.
+    #[must_use]
+    pub fn of_decayed_sphere(
+        node_min: IVec3,
+        size: u32,
+        pos: IVec3,
+        r: u32,
+        decay: f32,
+        salt: u64,
+    ) -> Self {
+        match Self::of_sphere(node_min, size, pos, r) {
+            Self::Outside => Self::Outside,
+            coverage if decay <= 0.0 => coverage,
+            _ if size > 1 => Self::Partial,
+            _ if hash_unit(salt, node_min) <= decay => Self::Outside,
+            _ => Self::Inside,
+        }
+    }
+}
+
+/// Hashes a position into a value in `0.0..1.0`, the same `salt` and `pos` always give
+/// the same value.
+#[must_use]
+pub fn hash_unit(salt: u64, pos: IVec3) -> f32 {
+    (hash_pos(salt, pos) >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// Mixes the position coordinates into `salt`, one SplitMix64 round each.
+#[must_use]
+pub fn hash_pos(salt: u64, pos: IVec3) -> u64 {
+    pos.to_array()
+        .into_iter()
+        .fold(salt, |hash, c| splitmix64(hash ^ u64::from(c as u32)))
+}
+
+const fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+//= FILL ===========================================================================================
+
+/// Octree-aware bulk operations.
+///
+/// Nodes completely covered by the region are replaced by a single leaf,
+/// only the nodes on the boundary are split and visited down to the voxels.
+impl World {
+    /// Fills the box going from `a` to `b`, both inclusive and in any order.
+    pub fn fill_box(&mut self, a: IVec3, b: IVec3, voxel: Voxel) -> Result<Vec<NodeSeq>, WorldErr> {
+        let min = a.min(b);
+        let max = a.max(b);
+        self.fill_shape(voxel, |node_min, size| {
+            Coverage::of_box(node_min, size, min, max)
+        })
+    }
+
+    /// Fills the voxels covered by a shape, described by its `Coverage` of a node given
+    /// the node min corner and size.
+    ///
+    /// Voxel sized nodes (size of one) returned as `Coverage::Partial` are filled.
+    ///
+    /// Fails if the world runs out of nodes, the voxels filled until then stay filled.
+    pub fn fill_shape(
+        &mut self,
+        voxel: Voxel,
+        coverage: impl Fn(IVec3, u32) -> Coverage,
+    ) -> Result<Vec<NodeSeq>, WorldErr> {
+        let mut result = vec![];
+        self.fill_node(0, self.min, self.size, voxel, &coverage, &mut result)?;
+        Ok(coalesce_node_seqs(result))
+    }
+
+    fn fill_node(
+        &mut self,
+        idx: u32,
+        node_min: IVec3,
+        size: u32,
+        voxel: Voxel,
+        coverage: &impl Fn(IVec3, u32) -> Coverage,
+        result: &mut Vec<NodeSeq>,
+    ) -> Result<(), WorldErr> {
+        let node = self.get_node(idx);
+        let already_filled = !node.is_split() && node.get_voxel() == voxel;
+
+        match coverage(node_min, size) {
+            Coverage::Outside => Ok(()),
+            _ if already_filled => Ok(()),
+            Coverage::Inside => {
+                self.fill_leaf(idx, voxel, result);
+                Ok(())
+            }
+            Coverage::Partial if size == 1 => {
+                self.fill_leaf(idx, voxel, result);
+                Ok(())
+            }
+            Coverage::Partial => {
+                if !node.is_split() {
+                    let first_child = self.new_nodes(node.get_voxel())?;
+                    self.mut_node(idx).split(first_child);
+                    result.push(NodeSeq { idx, count: 1 });
+                    result.push(NodeSeq {
+                        idx: first_child,
+                        count: 8,
+                    });
+                }
+
+                // Shared children are copied before being modified.
+                let first_child = self.unshare_children(idx, result)?;
+                let half = size / 2;
+                let mut filled = Ok(());
+                for i in 0..8 {
+                    let child_min = node_min
+                        + ivec3(i as i32 & 1, i as i32 >> 1 & 1, i as i32 >> 2) * half as i32;
+                    filled =
+                        self.fill_node(first_child + i, child_min, half, voxel, coverage, result);
+                    if filled.is_err() {
+                        break;
+                    }
+                }
+
+                // Even when out of nodes, so the splits left uniform don't stay around.
+                if let Some(voxel) = self.can_simplify(idx) {
+                    self.free_nodes(first_child);
+                    self.mut_node(idx).simplify(voxel);
+                    result.push(NodeSeq { idx, count: 1 });
+                }
+                filled
+            }
+        }
+    }
+
+    fn fill_leaf(&mut self, idx: u32, voxel: Voxel, result: &mut Vec<NodeSeq>) {
+        let node = self.get_node(idx);
+        if node.is_split() {
+            self.free_nodes(node.first_child());
+        }
+        *self.mut_node(idx) = Node::new_leaf(voxel);
+        result.push(NodeSeq { idx, count: 1 });
+    }
+}
+
+//= NODE SEQUENCES =================================================================================
+
+/// Sorts the sequences and merges the overlapping or adjacent ones,
+/// so that every modified node is uploaded once with the fewest writes.
+#[must_use]
+pub fn coalesce_node_seqs(mut seqs: Vec<NodeSeq>) -> Vec<NodeSeq> {
+    seqs.sort_unstable_by_key(|seq| seq.idx);
+
+    let mut result: Vec<NodeSeq> = Vec::with_capacity(seqs.len());
+    for seq in seqs {
+        if let Some(last) = result.last_mut() {
+            let last_end = last.idx + u32::from(last.count);
+            let seq_end = seq.idx + u32::from(seq.count);
+            if seq.idx <= last_end && seq_end - last.idx <= u32::from(u8::MAX) {
+                last.count = last.count.max((seq_end - last.idx) as u8);
+                continue;
+            }
+        }
+        result.push(seq);
+    }
+    result
+}
@@ -0,0 +1,811 @@
+//= IMPORTS ========================================================================================
+
+use crate::open_simplex::{NoiseMap, NoiseMap3d};
+use crate::world::{
+    Biome, BiomeRegistry, Coverage, NodeSeq, Schematic, Structure, World, WorldErr, hash_pos,
+    hash_unit,
+};
+
+use voxel_math::walker::walk_line;
+use voxel_render::Voxel;
+
+use fastrand::Rng;
+use glam::{IVec3, Vec3, ivec3, vec2, vec3};
+
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::channel;
+use std::thread;
+
+//= CONSTANTS ======================================================================================
+
+/// How far from its column, horizontally, a structure can grow.
+pub const STRUCTURE_MARGIN: i32 = 16;
+
+/// Side, in columns, of the square chunks generated by each worker thread.
+pub const CHUNK_SIZE: i32 = 64;
+
+/// Radius of the leaves growing at the end of every tree branch.
+pub const BRANCH_LEAVES_RADIUS: u32 = 3;
+
+/// Height of the water surface, the columns below it are flooded.
+pub const SEA_LEVEL: i32 = 26;
+
+//= WORLD GENERATION ===============================================================================
+
+#[derive(Clone)]
+pub struct NoiseMaps {
+    height: NoiseMap,
+    freq: NoiseMap,
+    scale: NoiseMap,
+    bumps: NoiseMap,
+    mountains: NoiseMap,
+    temp: NoiseMap,
+    moisture: NoiseMap,
+    vegetation: NoiseMap,
+    caverns: NoiseMap3d,
+    tunnels: [NoiseMap3d; 2],
+    veins: NoiseMap3d,
+    minerals: NoiseMap3d,
+}
+impl NoiseMaps {
+    pub fn from_seed(seed: i64) -> Self {
+        Self {
+            height: NoiseMap::new(seed.wrapping_mul(4326742), 0.003, 2.5),
+            freq: NoiseMap::new(seed.wrapping_mul(927144), 0.0001, 7.0),
+            scale: NoiseMap::new(seed.wrapping_mul(43265), 0.003, 40.0),
+            bumps: NoiseMap::new(seed.wrapping_mul(76324), 0.15, 4.0),
+            mountains: NoiseMap::new(seed.wrapping_mul(72316423), 0.001, 40.0),
+            temp: NoiseMap::new(seed.wrapping_mul(83226), 0.0004, 1.0),
+            moisture: NoiseMap::new(seed.wrapping_mul(2345632), 0.0004, 1.0),
+            vegetation: NoiseMap::new(seed.wrapping_mul(53252), 0.001, 1.0),
+            caverns: NoiseMap3d::new(seed.wrapping_mul(6328541), 0.02, 1.0),
+            tunnels: [
+                NoiseMap3d::new(seed.wrapping_mul(8835217), 0.015, 1.0),
+                NoiseMap3d::new(seed.wrapping_mul(1276433), 0.015, 1.0),
+            ],
+            veins: NoiseMap3d::new(seed.wrapping_mul(4418923), 0.06, 1.0),
+            minerals: NoiseMap3d::new(seed.wrapping_mul(9931057), 0.03, 1.0),
+        }
+    }
+
+    pub fn terrain_height(&self, x: f32, z: f32) -> f32 {
+        let freq = self.freq.get(vec2(x, z));
+        let scale = self.scale.get(vec2(x, z));
+        self.height.get(vec2(x * freq, z * freq)) * scale
+            + self.bumps.get(vec2(x, z))
+            + self.mountains.get(vec2(x, z))
+    }
+
+    pub fn temp(&self, x: f32, z: f32) -> f32 {
+        self.temp.get(vec2(x, z))
+    }
+
+    pub fn moisture(&self, x: f32, z: f32) -> f32 {
+        self.moisture.get(vec2(x, z))
+    }
+
+    pub fn vegetation(&self, x: f32, z: f32) -> f32 {
+        self.vegetation.get(vec2(x, z))
+    }
+
+    /// Caverns are squashed vertically, so they grow wider than taller.
+    pub fn cavern(&self, pos: IVec3) -> f32 {
+        self.caverns.get(pos.as_vec3() * vec3(1.0, 2.0, 1.0))
+    }
+
+    /// Tunnels follow the lines where both values are near the middle of their range.
+    pub fn tunnel(&self, pos: IVec3) -> (f32, f32) {
+        let pos = pos.as_vec3();
+        (self.tunnels[0].get(pos), self.tunnels[1].get(pos))
+    }
+
+    /// Veins follow the lines where the value is near the middle of its range.
+    pub fn vein(&self, pos: IVec3) -> f32 {
+        self.veins.get(pos.as_vec3())
+    }
+
+    /// Picks which ore fills a vein, changes slowly so every vein is mostly of one ore.
+    pub fn mineral(&self, pos: IVec3) -> f32 {
+        self.minerals.get(pos.as_vec3())
+    }
+}
+
+/// A row of the ore frequency table.
+pub struct OreGen {
+    pub ore: Voxel,
+    /// Heights where the ore can appear, its veins are most frequent halfway.
+    pub depth: Range<i32>,
+    /// Roughly the fraction of stone replaced by the ore halfway through `depth`.
+    pub frequency: f32,
+}
+
+impl OreGen {
+    /// The frequency at height `y`, falling linearly to zero at both ends of `depth`.
+    fn frequency_at(&self, y: i32) -> f32 {
+        if !self.depth.contains(&y) {
+            return 0.0;
+        }
+        let half = (self.depth.end - self.depth.start) as f32 * 0.5;
+        let middle = self.depth.start as f32 + half;
+        self.frequency * (1.0 - (y as f32 + 0.5 - middle).abs() / half)
+    }
+}
+
+/// Knobs of the cave pass, the caves are carved where the 3D noise maps cross the thresholds.
+pub struct CaveGen {
+    /// Heights between which caves can be carved.
+    pub depth: Range<i32>,
+    /// Solid voxels always left between a cave and the surface.
+    pub roof: i32,
+    /// How much of the underground is opened by caverns, from 0 (none) to 1 (all).
+    pub cavern_density: f32,
+    /// How wide the tunnels are, from 0 (none) to 1 (all the underground).
+    pub tunnel_density: f32,
+    /// Caves below this height are flooded with magma instead of air.
+    pub magma_level: i32,
+}
+
+pub struct WorldGen {
+    pub seed: i64,
+    pub maps: NoiseMaps,
+    pub caves: CaveGen,
+    pub ores: Vec<OreGen>,
+    pub biomes: BiomeRegistry,
+    /// Worker threads used by `WorldGen::populate`, one generates on the calling thread.
+    pub threads: usize,
+}
+
+impl WorldGen {
+    pub fn new(seed: i64, biomes: BiomeRegistry) -> Self {
+        let caves = CaveGen {
+            depth: 1..96,
+            roof: 6,
+            cavern_density: 0.25,
+            tunnel_density: 0.08,
+            magma_level: 8,
+        };
+        let ores = vec![
+            OreGen {
+                ore: Voxel::COAL,
+                depth: 16..80,
+                frequency: 0.03,
+            },
+            OreGen {
+                ore: Voxel::COPPER,
+                depth: 20..64,
+                frequency: 0.02,
+            },
+            OreGen {
+                ore: Voxel::IRON,
+                depth: 1..56,
+                frequency: 0.02,
+            },
+            OreGen {
+                ore: Voxel::GOLD,
+                depth: 1..24,
+                frequency: 0.006,
+            },
+            OreGen {
+                ore: Voxel::CRYSTAL,
+                depth: 1..32,
+                frequency: 0.005,
+            },
+        ];
+        let maps = NoiseMaps::from_seed(seed);
+        Self {
+            seed,
+            maps,
+            caves,
+            ores,
+            biomes,
+            threads: 1,
+        }
+    }
+
+    /// Generates the columns going from `min` to `max` (exclusive).
+    ///
+    /// With more than one thread the region is split in chunks of columns, generated in
+    /// worlds of their own and then grafted into `world`, this needs the region to span the
+    /// whole world height and to be aligned to the chunks, otherwise it falls back to
+    /// `WorldGen::populate_serial`.
+    ///
+    /// Fails if the world runs out of nodes, leaving the region partly generated.
+    pub fn populate(&self, min: IVec3, max: IVec3, world: &mut World) -> Result<(), WorldErr> {
+        let chunk_size = CHUNK_SIZE.min(world.size as i32);
+        let aligned = |pos: IVec3| {
+            let rel = pos - world.min();
+            rel.x % chunk_size == 0 && rel.z % chunk_size == 0
+        };
+        let full_height = min.y == world.min().y && max.y == world.max().y;
+
+        if self.threads <= 1 || !full_height || !aligned(min) || !aligned(max) {
+            return self.populate_serial(min, max, world);
+        }
+
+        let chunks = (min.x..max.x)
+            .step_by(chunk_size as usize)
+            .flat_map(|x| {
+                (min.z..max.z)
+                    .step_by(chunk_size as usize)
+                    .map(move |z| (x, z))
+            })
+            .map(|(x, z)| ivec3(x, min.y, z))
+            .collect::<Vec<_>>();
+        let next_chunk = AtomicUsize::new(0);
+        let (sender, receiver) = channel();
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads.min(chunks.len()) {
+                let sender = sender.clone();
+                let (chunks, next_chunk) = (&chunks, &next_chunk);
+                let max_depth = world.max_depth;
+                scope.spawn(move || {
+                    while let Some(&chunk_min) =
+                        chunks.get(next_chunk.fetch_add(1, Ordering::Relaxed))
+                    {
+                        let chunk = self.generate_chunk(chunk_min, chunk_size, max_depth);
+                        if sender.send((chunk_min, chunk)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            // Chunks are grafted as soon as they are ready, while the others are generated,
+            // dropping the receiver on failure stops the workers.
+            for (chunk_min, chunk) in receiver {
+                world.graft_column(chunk_min, chunk_size as u32, &chunk)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Generates the square chunk of columns starting at `chunk_min`, through the whole height
+    /// of a world of `max_depth`, in a world of its own.
+    pub fn generate_chunk(&self, chunk_min: IVec3, chunk_size: i32, max_depth: u32) -> World {
+        let mut chunk = World::new_region(chunk_min, max_depth);
+        let chunk_max = chunk_min + ivec3(chunk_size, 1 << max_depth, chunk_size);
+        self.populate_serial(chunk_min, chunk_max, &mut chunk)
+            .expect("regions grow on demand, they can't run out of nodes");
+        chunk
+    }
+
+    /// Generates the columns going from `min` to `max` (exclusive) on the calling thread.
+    ///
+    /// Every column draws from its own RNG, derived from the seed and the column coordinates,
+    /// and the structures growing near the region are clipped to it,
+    /// so populating adjacent regions in any order gives the same world.
+    ///
+    /// Fails if the world runs out of nodes, leaving the region partly generated.
+    pub fn populate_serial(
+        &self,
+        min: IVec3,
+        max: IVec3,
+        world: &mut World,
+    ) -> Result<(), WorldErr> {
+        let columns = (min.x..max.x)
+            .flat_map(|x| (min.z..max.z).map(move |z| (x, z)))
+            .map(|(x, z)| {
+                let y = self.maps.terrain_height(x as f32, z as f32) as i32;
+                (y, self.biome(x, z))
+            })
+            .collect::<Vec<_>>();
+
+        // The stone below the lowest column is filled at once, so the octree keeps it
+        // as few big nodes instead of splitting it column by column.
+        let lowest = columns
+            .iter()
+            .map(|(y, biome)| y - biome.depth())
+            .min()
+            .unwrap_or(min.y);
+        if lowest - 2 >= min.y {
+            world.fill_box(
+                ivec3(min.x, min.y, min.z),
+                ivec3(max.x - 1, lowest - 2, max.z - 1),
+                Voxel::STONE,
+            )?;
+        }
+
+        let mut columns = columns.into_iter();
+        for x in min.x..max.x {
+            for z in min.z..max.z {
+                let (y, biome) = columns.next().unwrap();
+                let surface_pos = ivec3(x, y, z);
+                let stone_top = y - biome.depth() - 1;
+
+                world.fill_box(
+                    ivec3(x, lowest - 1, z),
+                    ivec3(x, stone_top, z),
+                    Voxel::STONE,
+                )?;
+                let mut layer_top = y - 1;
+                for &(voxel, thickness) in &biome.layers {
+                    let layer_bottom = layer_top - thickness as i32 + 1;
+                    world.fill_box(ivec3(x, layer_bottom, z), ivec3(x, layer_top, z), voxel)?;
+                    layer_top = layer_bottom - 1;
+                }
+                self.place_ores(x, z, stone_top, world)?;
+                self.carve_caves(x, z, y, world)?;
+
+                if y < SEA_LEVEL {
+                    in_bounds(world.set_voxel(surface_pos, Voxel::SAND))?;
+                    let sea_top = ivec3(x, SEA_LEVEL, z);
+                    world.fill_box(surface_pos + IVec3::Y, sea_top, Voxel::WATER)?;
+                    continue;
+                }
+
+                in_bounds(world.set_voxel(surface_pos, biome.surface))?;
+            }
+        }
+
+        // Structures are placed once all the terrain is in place,
+        // including the ones growing from the columns around the region.
+        let mut region = Region {
+            world,
+            min,
+            max,
+            failed: None,
+        };
+        for x in min.x - STRUCTURE_MARGIN..max.x + STRUCTURE_MARGIN {
+            for z in min.z - STRUCTURE_MARGIN..max.z + STRUCTURE_MARGIN {
+                let y = self.maps.terrain_height(x as f32, z as f32) as i32;
+                if y < SEA_LEVEL {
+                    continue;
+                }
+                let surface_pos = ivec3(x, y, z);
+                let vegetation = self.maps.vegetation(x as f32, z as f32);
+                let mut rng = self.column_rng(x, z);
+
+                let rule = self
+                    .biome(x, z)
+                    .vegetation
+                    .iter()
+                    .find(|rule| rng.f32() < rule.chance * vegetation);
+                match rule.map(|rule| &rule.structure) {
+                    Some(Structure::Tree(tree)) => {
+                        spawn_tree(&mut region, surface_pos, tree, &mut rng)
+                    }
+                    Some(Structure::Cactus) => spawn_cactus(&mut region, surface_pos, &mut rng),
+                    Some(Structure::Spruce) => {
+                        spawn_spruce_tree(&mut region, surface_pos, &mut rng)
+                    }
+                    Some(Structure::Schematic {
+                        schematic,
+                        offset_y,
+                    }) => spawn_schematic(&mut region, surface_pos, schematic, *offset_y, &mut rng),
+                    None => {}
+                }
+            }
+        }
+        region.failed.map_or(Ok(()), Err)
+    }
+
+    /// Places the ore veins in the stone of a column, up to `top` inclusive.
+    fn place_ores(&self, x: i32, z: i32, top: i32, world: &mut World) -> Result<(), WorldErr> {
+        let start = self
+            .ores
+            .iter()
+            .map(|ore| ore.depth.start)
+            .min()
+            .unwrap_or(0);
+        let end = self.ores.iter().map(|ore| ore.depth.end).max().unwrap_or(0);
+
+        for y in start.max(world.min().y)..end.min(top + 1) {
+            let pos = ivec3(x, y, z);
+            if let Some(ore) = self.ore_at(pos) {
+                in_bounds(world.set_voxel(pos, ore))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn ore_at(&self, pos: IVec3) -> Option<Voxel> {
+        let total = self
+            .ores
+            .iter()
+            .map(|ore| ore.frequency_at(pos.y))
+            .sum::<f32>();
+        if total <= 0.0 || (self.maps.vein(pos) - 0.5).abs() >= total * 0.5 {
+            return None;
+        }
+
+        // Walk the frequencies at this height until the mineral value is reached.
+        // The noise rarely strays far from the middle, spread it so the first and last ores
+        // in the table get their share.
+        let mineral = (self.maps.mineral(pos) - 0.5) * 2.0 + 0.5;
+        let mut pick = mineral.clamp(0.0, 1.0) * total;
+        let mut last = None;
+        for ore in &self.ores {
+            let frequency = ore.frequency_at(pos.y);
+            if frequency > 0.0 {
+                pick -= frequency;
+                last = Some(ore.ore);
+                if pick <= 0.0 {
+                    break;
+                }
+            }
+        }
+        last
+    }
+
+    /// Carves the caves of a column whose surface is at `surface`,
+    /// every run of cave voxels is filled at once.
+    fn carve_caves(&self, x: i32, z: i32, surface: i32, world: &mut World) -> Result<(), WorldErr> {
+        let top = self.caves.depth.end.min(surface - self.caves.roof);
+
+        let mut run_start = self.caves.depth.start;
+        let mut run_voxel = None;
+        for y in self.caves.depth.start..=top {
+            let pos = ivec3(x, y, z);
+            let flood = if y < self.caves.magma_level {
+                Voxel::MAGMA
+            } else {
+                Voxel::AIR
+            };
+            let voxel = (y < top && self.is_cave(pos)).then_some(flood);
+            if voxel != run_voxel {
+                if let Some(run_voxel) = run_voxel {
+                    world.fill_box(ivec3(x, run_start, z), pos - IVec3::Y, run_voxel)?;
+                }
+                run_start = y;
+                run_voxel = voxel;
+            }
+        }
+        Ok(())
+    }
+
+    fn is_cave(&self, pos: IVec3) -> bool {
+        if self.maps.cavern(pos) > 1.0 - self.caves.cavern_density {
+            return true;
+        }
+        let half_width = self.caves.tunnel_density * 0.5;
+        let (a, b) = self.maps.tunnel(pos);
+        (a - 0.5).abs() < half_width && (b - 0.5).abs() < half_width
+    }
+
+    /// The biome of a column, near the edge between two biomes the climate is jittered
+    /// by up to `BiomeRegistry::blend` so their voxels and vegetation mix.
+    fn biome(&self, x: i32, z: i32) -> &Biome {
+        let jitter = |salt: i32| {
+            (hash_unit(self.seed as u64, ivec3(x, salt, z)) * 2.0 - 1.0) * self.biomes.blend
+        };
+        let temp = self.maps.temp(x as f32, z as f32) + jitter(1);
+        let moisture = self.maps.moisture(x as f32, z as f32) + jitter(2);
+        self.biomes.biome_at(temp, moisture)
+    }
+
+    fn column_rng(&self, x: i32, z: i32) -> Rng {
+        Rng::with_seed(hash_pos(self.seed as u64, ivec3(x, 0, z)))
+    }
+}
+
+/// The part of the world being populated, writes falling outside of it are dropped.
+struct Region<'a> {
+    world: &'a mut World,
+    min: IVec3,
+    max: IVec3,
+    /// The first write that failed, the structures keep being placed as far as they can.
+    failed: Option<WorldErr>,
+}
+
+impl Region<'_> {
+    fn fill_shape(&mut self, voxel: Voxel, coverage: impl Fn(IVec3, u32) -> Coverage) {
+        let min = self.min;
+        let max = self.max - IVec3::ONE;
+        let result = self.world.fill_shape(voxel, |node_min, size| {
+            coverage(node_min, size).min(Coverage::of_box(node_min, size, min, max))
+        });
+        if let Err(e) = result {
+            self.failed.get_or_insert(e);
+        }
+    }
+
+    fn set_voxel(&mut self, pos: IVec3, voxel: Voxel) {
+        self.fill_box(pos, pos, voxel);
+    }
+
+    fn fill_box(&mut self, a: IVec3, b: IVec3, voxel: Voxel) {
+        let min = a.min(b);
+        let max = a.max(b);
+        self.fill_shape(voxel, |node_min, size| {
+            Coverage::of_box(node_min, size, min, max)
+        });
+    }
+
+    fn sphere(&mut self, pos: IVec3, r: u32, voxel: Voxel, decay: f32, rng: &mut Rng) {
+        let salt = rng.u64(..);
+        self.fill_shape(voxel, |node_min, size| {
+            Coverage::of_decayed_sphere(node_min, size, pos, r, decay, salt)
+        });
+    }
+
+    fn bounded_sphere(&mut self, pos: IVec3, r: u32, voxel: Voxel, min: IVec3, max: IVec3) {
+        self.fill_shape(voxel, |node_min, size| {
+            Coverage::of_box(node_min, size, min, max)
+                .min(Coverage::of_sphere(node_min, size, pos, r))
+        });
+    }
+}
+
+fn spawn_cactus(region: &mut Region, pos: IVec3, rng: &mut Rng) {
+    let pos = pos + IVec3::Y;
+    let height = rng.i32(2..7);
+    let splits = if height > 3 { rng.u32(0..4) } else { 0 };
+
+    region.fill_box(pos, pos + IVec3::Y * height, Voxel::CACTUS);
+    for _ in 0..splits {
+        let split_h = rng.i32(1..height);
+        let split_len = rng.i32(1..4);
+        let dir = rand_cardinal_dir(rng);
+
+        region.set_voxel(pos + IVec3::Y * split_h + dir, Voxel::CACTUS);
+        region.fill_box(
+            pos + IVec3::Y * split_h + dir * 2,
+            pos + IVec3::Y * (split_h + split_len) + dir * 2,
+            Voxel::CACTUS,
+        );
+    }
+}
+
+fn spawn_spruce_tree(region: &mut Region, pos: IVec3, rng: &mut Rng) {
+    let offset = rng.i32(4..8);
+    let height = offset + rng.i32(10..18);
+
+    let mut y = height;
+    let mut r: i32 = 1;
+    while y > offset {
+        let c = pos + IVec3::Y * y;
+        let min = c - ivec3(r, 0, r);
+        let max = c + ivec3(r, 0, r);
+        region.bounded_sphere(c, r as u32, Voxel::SPRUCE_LEAVES, min, max);
+
+        r += 1;
+        y -= 2;
+    }
+    region.fill_box(pos, pos + IVec3::Y * (height - 1), Voxel::SPRUCE_WOOD);
+}
+
+fn spawn_schematic(
+    region: &mut Region,
+    surface: IVec3,
+    schematic: &Schematic,
+    offset_y: i32,
+    rng: &mut Rng,
+) {
+    let schematic = schematic.rotated(rng.i32(0..4));
+    let size = schematic.size();
+    let min = surface + ivec3(-size.x / 2, offset_y, -size.z / 2);
+    for (pos, voxel) in schematic.voxels() {
+        if voxel != Voxel::AIR {
+            region.set_voxel(min + pos, voxel);
+        }
+    }
+}
+
+pub struct TreeGen {
+    pub height: Range<u32>,
+    pub bark: Voxel,
+    pub leaves: Voxel,
+    pub leaves_decay: f32,
+    pub branch_count: Range<u32>,
+    pub branch_height: Range<f32>,
+    pub branch_len: Range<f32>,
+}
+
+fn spawn_tree(region: &mut Region, surface: IVec3, tree: &TreeGen, rng: &mut Rng) {
+    let height = rng.u32(tree.height.clone());
+    let randf32 = |rng: &mut Rng, range: Range<f32>| -> f32 {
+        let size = range.end - range.start;
+        rng.f32() * size + range.start
+    };
+
+    // only create branches if the tree is tall
+    let branch_count = if height < 11 {
+        0
+    } else {
+        rng.u32(tree.branch_count.clone())
+    };
+
+    region.sphere(
+        surface + ivec3(0, height as i32, 0),
+        5,
+        tree.leaves,
+        tree.leaves_decay,
+        rng,
+    );
+
+    for _ in 0..branch_count {
+        let branch_h = (randf32(rng, tree.branch_height.clone()) * height as f32) as u32;
+        let branch_len = randf32(rng, tree.branch_len.clone());
+
+        let branch_dir = rand_hem_dir(Vec3::Y, rng);
+        let start = ivec3(surface.x, surface.y + branch_h as i32, surface.z);
+        let end = (start.as_vec3() + branch_dir * branch_len).as_ivec3();
+
+        region.sphere(
+            end,
+            BRANCH_LEAVES_RADIUS,
+            tree.leaves,
+            tree.leaves_decay,
+            rng,
+        );
+
+        let line = walk_line(start, end);
+        for pos in line {
+            region.set_voxel(pos, tree.bark);
+        }
+    }
+
+    for i in 0..height as i32 {
+        region.set_voxel(surface + ivec3(0, i, 0), tree.bark);
+    }
+}
+
+/// Writes outside the world are expected while generating, only running out of nodes fails.
+fn in_bounds(result: Result<Vec<NodeSeq>, WorldErr>) -> Result<(), WorldErr> {
+    match result {
+        Err(WorldErr::OutOfBounds) | Ok(_) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn rand_cardinal_dir(rng: &mut Rng) -> IVec3 {
+    [
+        ivec3(-1, 0, 0),
+        ivec3(1, 0, 0),
+        ivec3(0, 0, -1),
+        ivec3(0, 0, 1),
+    ][rng.usize(0..4)]
+}
+
+pub fn rand_dir(rng: &mut Rng) -> Vec3 {
+    fn rand_norm(rng: &mut Rng) -> f32 {
+        let theta = 2.0 * std::f32::consts::PI * rng.f32();
+        let rho = (-2.0 * rng.f32().ln()).sqrt();
+        rho * theta.cos()
+    }
+
+    let x = rand_norm(rng);
+    let y = rand_norm(rng);
+    let z = rand_norm(rng);
+    vec3(x, y, z).normalize()
+}
+
+pub fn rand_hem_dir(norm: Vec3, rng: &mut Rng) -> Vec3 {
+    let dir = rand_dir(rng);
+    dir * norm.dot(dir).signum()
+}
+
+//= TESTS ==========================================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::world::{BiomeRegistry, World, WorldGen};
+
+    use voxel_config::BiomesConfig;
+    use voxel_render::Voxel;
+
+    use glam::{IVec3, ivec3};
+
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    fn nodes_hash(world: &World) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        world
+            .nodes()
+            .iter()
+            .for_each(|node| node.raw().hash(&mut hasher));
+        hasher.finish()
+    }
+
+    #[test]
+    fn populate_is_deterministic() {
+        crate::open_simplex::init_gradients();
+        let world_gen = || {
+            WorldGen::new(
+                7,
+                BiomeRegistry::from_config(&BiomesConfig::default()).unwrap(),
+            )
+        };
+        let min = ivec3(128, 0, -128);
+        let generate = |halves: &[(i32, i32)]| {
+            let mut world = World::new_region(min, 7);
+            let world_gen = world_gen();
+            for &(from, to) in halves {
+                let (a, b) = (
+                    world.min().with_x(min.x + from),
+                    world.max().with_x(min.x + to),
+                );
+                world_gen.populate_serial(a, b, &mut world).unwrap();
+            }
+            world
+        };
+
+        let first = generate(&[(0, 128)]);
+        assert_eq!(nodes_hash(&first), nodes_hash(&generate(&[(0, 128)])));
+
+        // The halves don't know about each other, they must still agree on their border.
+        let forward = generate(&[(0, 64), (64, 128)]);
+        let backward = generate(&[(64, 128), (0, 64)]);
+        assert_eq!(
+            nodes_hash(&forward),
+            nodes_hash(&generate(&[(0, 64), (64, 128)]))
+        );
+        for x in 0..128 {
+            for y in 0..128 {
+                for z in 0..128 {
+                    let pos = min + IVec3::new(x, y, z);
+                    let voxel = first.get_voxel(pos).unwrap();
+                    assert!(forward.get_voxel(pos).unwrap() == voxel, "{pos}");
+                    assert!(backward.get_voxel(pos).unwrap() == voxel, "{pos}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_populate_matches_serial() {
+        crate::open_simplex::init_gradients();
+        let biomes = || BiomeRegistry::from_config(&BiomesConfig::default()).unwrap();
+        let min = ivec3(-64, 0, 192);
+
+        let mut serial = World::new_region(min, 7);
+        WorldGen::new(42, biomes())
+            .populate_serial(serial.min(), serial.max(), &mut serial)
+            .unwrap();
+
+        let mut parallel = World::new_region(min, 7);
+        let mut world_gen = WorldGen::new(42, biomes());
+        world_gen.threads = 4;
+        world_gen
+            .populate(parallel.min(), parallel.max(), &mut parallel)
+            .unwrap();
+
+        for x in 0..128 {
+            for y in 0..128 {
+                for z in 0..128 {
+                    let pos = min + IVec3::new(x, y, z);
+                    assert!(
+                        serial.get_voxel(pos).unwrap() == parallel.get_voxel(pos).unwrap(),
+                        "voxels differ at {pos}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn populate_below_the_origin() {
+        crate::open_simplex::init_gradients();
+        let biomes = || BiomeRegistry::from_config(&BiomesConfig::default()).unwrap();
+
+        let mut above = World::new_region(ivec3(0, 0, 64), 7);
+        WorldGen::new(5, biomes())
+            .populate_serial(above.min(), above.max(), &mut above)
+            .unwrap();
+
+        // Twice as tall, going as far below zero as the other goes above.
+        let min = ivec3(0, -128, 64);
+        let mut deep = World::new_region(min, 8);
+        WorldGen::new(5, biomes())
+            .populate_serial(deep.min(), ivec3(128, 128, 192), &mut deep)
+            .unwrap();
+
+        for x in 0..128 {
+            for z in 64..192 {
+                let bottom = deep.get_voxel(ivec3(x, min.y, z)).unwrap();
+                assert!(bottom != Voxel::AIR, "no ground at {x} {z}");
+                for y in 0..128 {
+                    let pos = ivec3(x, y, z);
+                    let voxel = above.get_voxel(pos).unwrap();
+                    assert!(
+                        deep.get_voxel(pos).unwrap() == voxel,
+                        "voxels differ at {pos}"
+                    );
+                }
+            }
+        }
+    }
+}
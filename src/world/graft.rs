@@ -0,0 +1,82 @@
+//= IMPORTS ========================================================================================
+
+use crate::world::{FoundNode, NodeSeq, World, WorldErr, coalesce_node_seqs};
+
+use voxel_render::{Node, Voxel};
+
+use glam::IVec3;
+
+//= GRAFT ==========================================================================================
+
+/// Copy whole subtrees between worlds.
+impl World {
+    /// Replaces the node of `size` containing `pos` with a copy of the same node of `src`.
+    ///
+    /// `size` must be a power of two no bigger than both worlds, and `pos` must be aligned to it
+    /// relative to the min of both worlds. If the world runs out of nodes the node is left
+    /// partly copied.
+    pub fn graft(&mut self, pos: IVec3, size: u32, src: &Self) -> Result<Vec<NodeSeq>, WorldErr> {
+        let depth = (self.size / size).trailing_zeros();
+        let src_depth = (src.size / size).trailing_zeros();
+        let FoundNode { idx: src_idx, .. } = src.find_node(pos, src_depth)?;
+
+        let mut result = vec![];
+        let idx = match self.split_down(pos, depth, &mut result) {
+            Ok(idx) => idx,
+            Err(e) => {
+                self.merge_parents(pos, &mut result);
+                return Err(e);
+            }
+        };
+        let node = self.get_node(idx);
+        if node.is_split() {
+            self.free_nodes(node.first_child());
+            *self.mut_node(idx) = Node::new_leaf(Voxel::AIR);
+        }
+        self.copy_node(idx, src, src_idx, &mut result)?;
+
+        self.merge_parents(pos, &mut result);
+        Ok(coalesce_node_seqs(result))
+    }
+
+    /// Grafts the column of nodes of `size` going from `pos` up through the whole world height.
+    pub fn graft_column(
+        &mut self,
+        pos: IVec3,
+        size: u32,
+        src: &Self,
+    ) -> Result<Vec<NodeSeq>, WorldErr> {
+        let mut result = vec![];
+        for y in (self.min.y..self.max().y).step_by(size as usize) {
+            result.extend(self.graft(pos.with_y(y), size, src)?);
+        }
+        Ok(coalesce_node_seqs(result))
+    }
+
+    /// The node at `idx` must be a leaf, so it's left valid if the world runs out of nodes.
+    fn copy_node(
+        &mut self,
+        idx: u32,
+        src: &Self,
+        src_idx: u32,
+        result: &mut Vec<NodeSeq>,
+    ) -> Result<(), WorldErr> {
+        let src_node = src.get_node(src_idx);
+        result.push(NodeSeq { idx, count: 1 });
+        if !src_node.is_split() {
+            *self.mut_node(idx) = Node::new_leaf(src_node.get_voxel());
+            return Ok(());
+        }
+
+        let first_child = self.new_nodes(Voxel::AIR)?;
+        *self.mut_node(idx) = Node::new_split(first_child);
+        result.push(NodeSeq {
+            idx: first_child,
+            count: 8,
+        });
+        for i in 0..8 {
+            self.copy_node(first_child + i, src, src_node.get_child(i), result)?;
+        }
+        Ok(())
+    }
+}
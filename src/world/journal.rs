@@ -0,0 +1,344 @@
+//= IMPORTS ========================================================================================
+
+use crate::world::{NodeSeq, World, WorldErr, coalesce_node_seqs};
+
+use voxel_render::Voxel;
+
+use fastrand::Rng;
+use glam::IVec3;
+
+use std::collections::VecDeque;
+
+//= CONSTANTS ======================================================================================
+
+/// Transactions kept by default, the oldest ones are forgotten first.
+pub const MAX_TRANSACTIONS: usize = 256;
+
+//= EDIT JOURNAL ===================================================================================
+
+/// A box of voxels of the same type, going from `min` to `max` (exclusive).
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct VoxelBox {
+    pub min: IVec3,
+    pub max: IVec3,
+    pub voxel: Voxel,
+}
+
+/// A box of the world changed by an edit, as the leaves covering it before and after,
+/// so its size follows the nodes rather than the voxels.
+#[derive(Clone)]
+pub struct Edit {
+    pub old: Vec<VoxelBox>,
+    pub new: Vec<VoxelBox>,
+}
+
+/// Records the edits made through it, so they can be undone and redone.
+///
+/// Every edit belongs to a transaction, undone and redone as a whole: the voxels changed by
+/// one call, or all the voxels changed between `EditJournal::begin` and `EditJournal::commit`.
+pub struct EditJournal {
+    /// Transactions kept in the undo history.
+    pub max_transactions: usize,
+    undo: VecDeque<Vec<Edit>>,
+    redo: Vec<Vec<Edit>>,
+    /// The transaction opened by `EditJournal::begin`, if any.
+    open: Option<Vec<Edit>>,
+}
+
+impl Default for EditJournal {
+    fn default() -> Self {
+        Self {
+            max_transactions: MAX_TRANSACTIONS,
+            undo: VecDeque::new(),
+            redo: vec![],
+            open: None,
+        }
+    }
+}
+
+impl EditJournal {
+    //- Transactions -------------------------------------------------------------------------------
+
+    /// Groups the following edits into one transaction, until `EditJournal::commit`.
+    pub fn begin(&mut self) {
+        self.commit();
+        self.open = Some(vec![]);
+    }
+
+    /// Closes the transaction opened by `EditJournal::begin`.
+    pub fn commit(&mut self) {
+        if let Some(edits) = self.open.take() {
+            self.push(edits);
+        }
+    }
+
+    fn record(&mut self, edits: Vec<Edit>) {
+        match &mut self.open {
+            Some(open) => open.extend(edits),
+            None => self.push(edits),
+        }
+    }
+
+    fn push(&mut self, edits: Vec<Edit>) {
+        if edits.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        self.undo.push_back(edits);
+        while self.undo.len() > self.max_transactions {
+            self.undo.pop_front();
+        }
+    }
+
+    #[must_use]
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    #[must_use]
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Forgets every transaction.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.open = None;
+    }
+
+    //- Edits --------------------------------------------------------------------------------------
+
+    pub fn set_voxel(
+        &mut self,
+        world: &mut World,
+        pos: IVec3,
+        voxel: Voxel,
+    ) -> Result<Vec<NodeSeq>, WorldErr> {
+        world.check_bounds(pos)?;
+        self.record_region(world, pos, pos + IVec3::ONE, |world| {
+            world.set_voxel(pos, voxel)
+        })
+    }
+
+    /// Like `World::fill_box`, as one transaction.
+    pub fn fill_box(
+        &mut self,
+        world: &mut World,
+        a: IVec3,
+        b: IVec3,
+        voxel: Voxel,
+    ) -> Result<Vec<NodeSeq>, WorldErr> {
+        let (min, max) = (a.min(b), a.max(b) + IVec3::ONE);
+        self.record_region(world, min, max, |world| world.fill_box(a, b, voxel))
+    }
+
+    /// Like `World::sphere`, as one transaction.
+    pub fn sphere(
+        &mut self,
+        world: &mut World,
+        pos: IVec3,
+        r: u32,
+        voxel: Voxel,
+        decay: f32,
+        rng: &mut Rng,
+    ) -> Result<Vec<NodeSeq>, WorldErr> {
+        let r_vec = IVec3::splat(r as i32);
+        self.record_region(world, pos - r_vec, pos + r_vec + IVec3::ONE, |world| {
+            world.sphere(pos, r, voxel, decay, rng)
+        })
+    }
+
+    /// Runs an operation that only changes the voxels going from `min` to `max` (exclusive),
+    /// recording the leaves of the region before and after it as one transaction,
+    /// even if it fails halfway.
+    pub fn record_region(
+        &mut self,
+        world: &mut World,
+        min: IVec3,
+        max: IVec3,
+        op: impl FnOnce(&mut World) -> Result<Vec<NodeSeq>, WorldErr>,
+    ) -> Result<Vec<NodeSeq>, WorldErr> {
+        let min = min.max(world.min());
+        let max = max.min(world.max());
+        if min.cmpge(max).any() {
+            return op(world);
+        }
+
+        let old = region_boxes(world, min, max);
+        let result = op(world);
+        let new = region_boxes(world, min, max);
+        if old != new {
+            self.record(vec![Edit { old, new }]);
+        }
+        result
+    }
+
+    //- Undo and Redo ------------------------------------------------------------------------------
+
+    /// Reverts the last transaction, returns the modified nodes.
+    ///
+    /// Edits that scrolled out of the world are skipped. If the world runs out of nodes
+    /// the transaction is left to undo, undoing it again completes it.
+    pub fn undo(&mut self, world: &mut World) -> Result<Vec<NodeSeq>, WorldErr> {
+        self.commit();
+        let Some(edits) = self.undo.pop_back() else {
+            return Ok(vec![]);
+        };
+        match apply(world, edits.iter().rev().map(|edit| &edit.old)) {
+            Ok(result) => {
+                self.redo.push(edits);
+                Ok(result)
+            }
+            Err(e) => {
+                self.undo.push_back(edits);
+                Err(e)
+            }
+        }
+    }
+
+    /// Applies again the last undone transaction, returns the modified nodes.
+    ///
+    /// Edits that scrolled out of the world are skipped. If the world runs out of nodes
+    /// the transaction is left to redo, redoing it again completes it.
+    pub fn redo(&mut self, world: &mut World) -> Result<Vec<NodeSeq>, WorldErr> {
+        self.commit();
+        let Some(edits) = self.redo.pop() else {
+            return Ok(vec![]);
+        };
+        match apply(world, edits.iter().map(|edit| &edit.new)) {
+            Ok(result) => {
+                self.undo.push_back(edits);
+                Ok(result)
+            }
+            Err(e) => {
+                self.redo.push(edits);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// The leaves of `world` overlapping the box from `min` to `max` (exclusive), cut to it.
+fn region_boxes(world: &World, min: IVec3, max: IVec3) -> Vec<VoxelBox> {
+    let mut boxes = vec![];
+    world.visit_region(min, max, |leaf| {
+        boxes.push(VoxelBox {
+            min: leaf.min.max(min),
+            max: leaf.max().min(max),
+            voxel: leaf.voxel,
+        });
+    });
+    boxes
+}
+
+/// Fills the boxes, the parts out of the world are skipped.
+fn apply<'a>(
+    world: &mut World,
+    boxes: impl Iterator<Item = &'a Vec<VoxelBox>>,
+) -> Result<Vec<NodeSeq>, WorldErr> {
+    let mut result = vec![];
+    for voxel_box in boxes.flatten() {
+        let seqs = world.fill_box(voxel_box.min, voxel_box.max - IVec3::ONE, voxel_box.voxel)?;
+        result.extend(seqs);
+    }
+    Ok(coalesce_node_seqs(result))
+}
+
+//= TESTS ==========================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use glam::ivec3;
+
+    #[test]
+    fn transactions_are_undone_and_redone_whole() {
+        let mut world = World::new_region(IVec3::ZERO, 6);
+        let mut journal = EditJournal::default();
+        let (a, b) = (ivec3(1, 2, 3), ivec3(40, 41, 42));
+
+        journal.begin();
+        journal.set_voxel(&mut world, a, Voxel::STONE).unwrap();
+        journal.set_voxel(&mut world, b, Voxel::SAND).unwrap();
+        journal.commit();
+        journal.set_voxel(&mut world, a, Voxel::DIRT).unwrap();
+
+        journal.undo(&mut world).unwrap();
+        assert!(world.get_voxel(a).unwrap() == Voxel::STONE);
+        assert!(world.get_voxel(b).unwrap() == Voxel::SAND);
+        journal.undo(&mut world).unwrap();
+        assert!(world.get_voxel(a).unwrap() == Voxel::AIR);
+        assert!(world.get_voxel(b).unwrap() == Voxel::AIR);
+        assert!(!journal.can_undo());
+
+        journal.redo(&mut world).unwrap();
+        assert!(world.get_voxel(a).unwrap() == Voxel::STONE);
+        assert!(world.get_voxel(b).unwrap() == Voxel::SAND);
+        assert!(journal.can_redo());
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let mut world = World::new_region(IVec3::ZERO, 6);
+        let mut journal = EditJournal::default();
+        journal
+            .set_voxel(&mut world, IVec3::ONE, Voxel::STONE)
+            .unwrap();
+        journal.undo(&mut world).unwrap();
+        assert!(journal.can_redo());
+
+        // Writing the voxel already there isn't an edit.
+        journal
+            .set_voxel(&mut world, IVec3::ONE, Voxel::AIR)
+            .unwrap();
+        assert!(journal.can_redo());
+
+        journal
+            .set_voxel(&mut world, IVec3::ZERO, Voxel::DIRT)
+            .unwrap();
+        assert!(!journal.can_redo());
+        journal.redo(&mut world).unwrap();
+        assert!(world.get_voxel(IVec3::ONE).unwrap() == Voxel::AIR);
+    }
+
+    #[test]
+    fn big_fills_record_leaves() {
+        let mut world = World::new_region(IVec3::ZERO, 9);
+        let mut rng = Rng::with_seed(1);
+        let mut journal = EditJournal::default();
+        journal
+            .sphere(
+                &mut world,
+                IVec3::splat(256),
+                40,
+                Voxel::STONE,
+                0.0,
+                &mut rng,
+            )
+            .unwrap();
+        let sphere = world.leaves().collect::<Vec<_>>();
+
+        journal
+            .fill_box(&mut world, IVec3::ZERO, IVec3::splat(300), Voxel::WATER)
+            .unwrap();
+        // Only the leaves on the border of the box are small.
+        let edit = &journal.undo[1][0];
+        let volume = 301usize.pow(3);
+        assert!((edit.old.len() + edit.new.len()) * 64 < volume);
+        assert!(
+            edit.new
+                .iter()
+                .all(|voxel_box| voxel_box.voxel == Voxel::WATER)
+        );
+
+        journal.undo(&mut world).unwrap();
+        assert!(world.leaves().collect::<Vec<_>>() == sphere);
+        journal.undo(&mut world).unwrap();
+        assert_eq!(
+            world.nodes().iter().filter(|node| node.is_used()).count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,635 @@
+//= MODS ===========================================================================================
+
+mod biome;
+mod compact;
+mod dag;
+mod fill;
+mod generation;
+mod graft;
+mod journal;
+mod save;
+mod schematic;
+mod stats;
+mod streaming;
+mod visit;
+mod vox;
+
+//= RE-EXPORTS =====================================================================================
+
+pub use biome::*;
+pub use compact::*;
+pub use fill::*;
+pub use generation::*;
+pub use journal::*;
+pub use save::*;
+pub use schematic::*;
+pub use stats::*;
+pub use streaming::*;
+pub use visit::*;
+pub use vox::*;
+
+//= IMPORTS ========================================================================================
+
+use crate::open_simplex::init_gradients;
+
+use voxel_math::aabb::Aabb;
+use voxel_render::{DirtyNodes, DirtyRanges, Node, Voxel};
+
+use fastrand::Rng;
+use glam::{IVec3, ivec3};
+
+//= CONSTANTS ======================================================================================
+
+pub const DEFAULT_WORLD_DEPTH: u32 = 9;
+/// Half of the world must hold whole generation chunks, see `CHUNK_SIZE`.
+pub const MIN_WORLD_DEPTH: u32 = 7;
+/// Past it the shaders lose precision on the voxel positions.
+pub const MAX_WORLD_DEPTH: u32 = 16;
+
+/// Nodes used on average by a column of generated voxels, with some headroom,
+/// to tell if the nodes budget is enough for a world.
+const NODES_PER_COLUMN: u64 = 24;
+
+//= WORLD ==========================================================================================
+
+#[derive(Clone, Debug)]
+pub enum WorldErr {
+    OutOfBounds,
+    OutOfNodes,
+    InvalidDepth(u32),
+    Io(String),
+    InvalidFormat,
+    UnsupportedVersion(u16),
+    ChecksumMismatch,
+}
+
+struct FoundNode {
+    idx: u32,
+}
+
+/// The structure that holds the entire interactable world, representing all voxels via a SVO.
+#[derive(Clone, Default)]
+pub struct World {
+    pub seed: i64,
+    pub min: IVec3,
+    pub size: u32,
+    pub max_nodes: u64,
+    pub max_depth: u32,
+    start_search: u32,
+    last_used_node: u32,
+
+    // Note: Removing items from the Vec is not good since
+    // some nodes may point to other nodes by index.
+    nodes: Vec<Node>,
+    /// Split nodes pointing to each group of 8 sibling nodes, indexed by `group(first_child)`.
+    /// Groups referenced more than once are shared by identical subtrees, see `World::deduplicate`.
+    refs: Vec<u32>,
+    /// Nodes modified since the last `Renderer::sync_world`.
+    dirty: DirtyRanges,
+}
+
+/// Create and clear worlds
+impl World {
+    /// A world of `2^world_depth` voxels per side, its nodes must fit in `max_buffer_sizes` bytes.
+    pub fn new(max_buffer_sizes: u64, world_depth: u32) -> Result<Self, WorldErr> {
+        let max_nodes = max_buffer_sizes / size_of::<Node>() as u64;
+        Self::check_depth(world_depth, max_nodes)?;
+        let world_size = 1 << world_depth;
+
+        init_gradients();
+
+        let mut nodes = vec![Node::ZERO; max_nodes as usize];
+        nodes[0] = Node::new_leaf(Voxel::AIR);
+        let mut dirty = DirtyRanges::default();
+        dirty.mark(0, 1);
+        Ok(Self {
+            seed: 0,
+            min: IVec3::ZERO,
+            size: world_size,
+            max_nodes,
+            max_depth: world_depth,
+            start_search: 1,
+            last_used_node: 0,
+            nodes,
+            refs: vec![],
+            dirty,
+        })
+    }
+
+    /// Fails if `depth` is out of `MIN_WORLD_DEPTH..=MAX_WORLD_DEPTH`,
+    /// or if a generated world of that depth is not expected to fit in `max_nodes`.
+    pub fn check_depth(depth: u32, max_nodes: u64) -> Result<(), WorldErr> {
+        if !(MIN_WORLD_DEPTH..=MAX_WORLD_DEPTH).contains(&depth) {
+            return Err(WorldErr::InvalidDepth(depth));
+        }
+        let columns = 1u64 << (depth * 2);
+        if columns * NODES_PER_COLUMN > max_nodes {
+            return Err(WorldErr::OutOfNodes);
+        }
+        Ok(())
+    }
+
+    /// A world starting at `min` whose node pool grows on demand, used to generate
+    /// a part of a bigger world away from it.
+    pub fn new_region(min: IVec3, max_depth: u32) -> Self {
+        Self {
+            seed: 0,
+            min,
+            size: 1 << max_depth,
+            max_nodes: u64::from(u32::MAX),
+            max_depth,
+            start_search: 1,
+            last_used_node: 0,
+            nodes: vec![Node::new_leaf(Voxel::AIR)],
+            refs: vec![],
+            dirty: DirtyRanges::default(),
+        }
+    }
+
+    pub fn min(&self) -> IVec3 {
+        self.min
+    }
+    pub fn max(&self) -> IVec3 {
+        self.min + IVec3::splat(self.size as i32)
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes[0..=self.last_used_node as usize]
+    }
+
+    /// Changes the depth of an empty world, see `World::check_depth`.
+    pub fn set_max_depth(&mut self, max_depth: u32) -> Result<(), WorldErr> {
+        Self::check_depth(max_depth, self.max_nodes)?;
+        self.max_depth = max_depth;
+        self.size = 1 << max_depth;
+        Ok(())
+    }
+
+    pub fn last_used_node(&self) -> u32 {
+        self.last_used_node
+    }
+
+    pub fn clear_node(&mut self, idx: u32) {
+        self.free_node(idx);
+        self.nodes[idx as usize] = Node::new_leaf(Voxel::AIR);
+        self.dirty.mark(idx, 1);
+    }
+
+    pub fn clear(&mut self) {
+        for node in &mut self.nodes {
+            node.set_used_flag(false);
+        }
+        self.nodes[0] = Node::new_leaf(Voxel::AIR);
+        self.refs.clear();
+        self.dirty.mark(0, self.last_used_node + 1);
+        self.start_search = 1;
+        self.last_used_node = 0;
+    }
+}
+impl DirtyNodes for World {
+    fn nodes(&self) -> &[Node] {
+        self.nodes()
+    }
+
+    fn dirty_ranges(&self) -> &DirtyRanges {
+        &self.dirty
+    }
+}
+
+/// Find and mutate the SVO nodes that make up the world.
+impl World {
+    pub fn check_bounds(&self, pos: IVec3) -> Result<(), WorldErr> {
+        let in_bounds = (pos.cmpge(self.min())).all() && (pos.cmplt(self.max())).all();
+        in_bounds.then_some(()).ok_or(WorldErr::OutOfBounds)
+    }
+
+    fn find_node(&self, pos: IVec3, max_depth: u32) -> Result<FoundNode, WorldErr> {
+        self.check_bounds(pos)?;
+
+        let mut center = self.min + IVec3::splat(self.size as i32 / 2);
+        let mut size = self.size;
+        let mut node_idx = 0;
+        let mut depth: u32 = 0;
+
+        loop {
+            let node = self.get_node(node_idx);
+            if !node.is_split() || depth == max_depth {
+                return Ok(FoundNode { idx: node_idx });
+            }
+            size /= 2;
+
+            let gt = ivec3(
+                (pos.x >= center.x) as i32,
+                (pos.y >= center.y) as i32,
+                (pos.z >= center.z) as i32,
+            );
+            let child_idx = (gt.x as u32) | (gt.y as u32) << 1 | (gt.z as u32) << 2;
+            node_idx = node.get_child(child_idx);
+            let child_dir = gt * 2 - IVec3::ONE;
+            center += IVec3::splat(size as i32 / 2) * child_dir;
+            depth += 1;
+        }
+    }
+
+    pub fn get_node(&self, idx: u32) -> Node {
+        self.nodes[idx as usize]
+    }
+
+    pub fn swap_nodes(&mut self, a: u32, b: u32) {
+        self.nodes.swap(b as usize, a as usize);
+        self.dirty.mark(a, 1);
+        self.dirty.mark(b, 1);
+    }
+
+    /// The node is marked as modified.
+    pub fn mut_node(&mut self, idx: u32) -> &mut Node {
+        self.dirty.mark(idx, 1);
+        &mut self.nodes[idx as usize]
+    }
+
+    /// Drops one reference to the 8 nodes starting from `start`,
+    /// they are freed with their children once no split node points to them.
+    pub fn free_nodes(&mut self, start: u32) {
+        if let Some(refs) = self.refs.get_mut(group(start)) {
+            *refs = refs.saturating_sub(1);
+            if *refs > 0 {
+                return;
+            }
+        }
+        if start < self.start_search {
+            self.start_search = start;
+        }
+        for idx in start..start + 8 {
+            self.free_node(idx);
+        }
+    }
+
+    pub fn free_node(&mut self, idx: u32) {
+        self.nodes[idx as usize].set_used_flag(false);
+        self.dirty.mark(idx, 1);
+        if self.nodes[idx as usize].is_split() {
+            self.free_nodes(self.nodes[idx as usize].first_child());
+        }
+    }
+
+    /// Allocates 8 sibling leaves of `voxel`, returns the first one.
+    ///
+    /// Fails if they would go past `World::max_nodes`, the nodes the GPU buffer can hold.
+    fn new_nodes(&mut self, voxel: Voxel) -> Result<u32, WorldErr> {
+        static NEW_NODES: [Node; 1024] = [Node::ZERO; 1024];
+
+        let mut result = self.start_search;
+        if result + 7 >= self.nodes.len() as u32 {
+            // result can at most be nodes.len(),
+            // so adding 8 more nodes should make result valid,
+            // but I add 1024 nodes here because it's very likely
+            // to want to allocate more nodes very soon after
+            self.nodes.extend(&NEW_NODES);
+        }
+
+        while self.get_node(result).is_used() {
+            result += 8;
+            if result + 7 >= self.nodes.len() as u32 {
+                self.nodes.extend(&NEW_NODES);
+            }
+        }
+        if u64::from(result) + 8 > self.max_nodes {
+            return Err(WorldErr::OutOfNodes);
+        }
+        self.start_search = result + 8;
+
+        for idx in result..result + 8 {
+            self.nodes[idx as usize] = Node::new_leaf(voxel);
+        }
+        if group(result) >= self.refs.len() {
+            self.refs.resize(group(result) + 1, 0);
+        }
+        self.refs[group(result)] = 1;
+        self.dirty.mark(result, 8);
+        if result > self.last_used_node.saturating_sub(7) {
+            self.last_used_node = result + 7;
+        }
+        Ok(result)
+    }
+
+    /// Gives the split node at `idx` its own copy of its children if they are shared
+    /// with other nodes, so they can be modified. Returns its first child.
+    fn unshare_children(&mut self, idx: u32, result: &mut Vec<NodeSeq>) -> Result<u32, WorldErr> {
+        let first_child = self.get_node(idx).first_child();
+        if self
+            .refs
+            .get(group(first_child))
+            .is_none_or(|&refs| refs <= 1)
+        {
+            return Ok(first_child);
+        }
+
+        let copy = self.new_nodes(Voxel::AIR)?;
+        for i in 0..8 {
+            let child = self.get_node(first_child + i);
+            self.nodes[(copy + i) as usize] = child;
+            if child.is_split() {
+                self.refs[group(child.first_child())] += 1;
+            }
+        }
+        self.refs[group(first_child)] -= 1;
+        self.mut_node(idx).set_first_child(copy);
+        result.push(NodeSeq { idx, count: 1 });
+        result.push(NodeSeq {
+            idx: copy,
+            count: 8,
+        });
+        Ok(copy)
+    }
+
+    /// Counts again the references to every group of nodes, walking the tree from the root.
+    fn recount_refs(&mut self) {
+        self.refs.clear();
+        self.refs.resize(group(self.last_used_node) + 1, 0);
+        let mut stack = vec![0];
+        while let Some(idx) = stack.pop() {
+            let node = self.get_node(idx);
+            if !node.is_split() {
+                continue;
+            }
+            let refs = &mut self.refs[group(node.first_child())];
+            *refs += 1;
+            // Shared children are visited only the first time.
+            if *refs == 1 {
+                stack.extend((0..8).map(|i| node.get_child(i)));
+            }
+        }
+    }
+}
+
+/// The index of the group of 8 sibling nodes starting from `first_child`, the root is in group 0
+/// on its own.
+const fn group(first_child: u32) -> usize {
+    first_child.div_ceil(8) as usize
+}
+
+#[derive(Clone, Copy)]
+pub struct NodeSeq {
+    pub idx: u32,
+    pub count: u8,
+}
+
+/// High-level voxel-based manipulation.
+impl World {
+    pub fn get_voxel(&self, pos: IVec3) -> Result<Voxel, WorldErr> {
+        let FoundNode { idx, .. } = self.find_node(pos, self.max_depth)?;
+        Ok(self.get_node(idx).get_voxel())
+    }
+
+    pub fn set_voxel(&mut self, pos: IVec3, voxel: Voxel) -> Result<Vec<NodeSeq>, WorldErr> {
+        let FoundNode { idx, .. } = self.find_node(pos, self.max_depth)?;
+        if !self.get_node(idx).is_split() && self.get_node(idx).get_voxel() == voxel {
+            return Ok(vec![]);
+        }
+
+        let mut result: Vec<NodeSeq> = vec![];
+        let idx = match self.split_down(pos, self.max_depth, &mut result) {
+            Ok(idx) => idx,
+            Err(e) => {
+                // Out of nodes, the splits done so far are undone.
+                self.merge_parents(pos, &mut result);
+                return Err(e);
+            }
+        };
+
+        // SVO now goes to desired depth, so we can mutate the node now.
+        self.mut_node(idx).set_voxel(voxel);
+        self.mut_node(idx).set_split_flag(false);
+        result.push(NodeSeq { idx, count: 1 });
+
+        self.merge_parents(pos, &mut result);
+        Ok(result)
+    }
+
+    /// Returns the node at `target_depth` containing `pos`.
+    ///
+    /// If the SVO doesn't go to the desired depth, the leaf found is split until it does,
+    /// its children keeping its voxel. The shared nodes along the way are copied,
+    /// so the returned node only belongs to this path.
+    fn split_down(
+        &mut self,
+        pos: IVec3,
+        target_depth: u32,
+        result: &mut Vec<NodeSeq>,
+    ) -> Result<u32, WorldErr> {
+        self.check_bounds(pos)?;
+
+        let mut center = self.min + IVec3::splat(self.size as i32 / 2);
+        let mut size = self.size;
+        let mut idx = 0;
+
+        for _ in 0..target_depth {
+            let first_child = if self.get_node(idx).is_split() {
+                self.unshare_children(idx, result)?
+            } else {
+                let first_child = self.new_nodes(self.get_node(idx).get_voxel())?;
+                self.mut_node(idx).split(first_child);
+                result.push(NodeSeq { idx, count: 1 });
+                result.push(NodeSeq {
+                    idx: first_child,
+                    count: 8,
+                });
+                first_child
+            };
+
+            size /= 2;
+
+            let gt = ivec3(
+                (pos.x >= center.x) as i32,
+                (pos.y >= center.y) as i32,
+                (pos.z >= center.z) as i32,
+            );
+            let child_idx = (gt.x as u32) | (gt.y as u32) << 1 | (gt.z as u32) << 2;
+            idx = first_child + child_idx;
+            let child_dir = gt * 2 - IVec3::ONE;
+            center += IVec3::splat(size as i32 / 2) * child_dir;
+        }
+        Ok(idx)
+    }
+
+    /// Walks back up from the leaf containing `pos` collapsing every parent whose
+    /// 8 children became the same leaf, so cleared regions don't keep deep chains of splits.
+    fn merge_parents(&mut self, pos: IVec3, result: &mut Vec<NodeSeq>) {
+        let mut parents = Vec::with_capacity(self.max_depth as usize);
+        let mut center = self.min + IVec3::splat(self.size as i32 / 2);
+        let mut size = self.size;
+        let mut node_idx = 0;
+
+        while self.get_node(node_idx).is_split() {
+            parents.push(node_idx);
+            size /= 2;
+
+            let gt = ivec3(
+                (pos.x >= center.x) as i32,
+                (pos.y >= center.y) as i32,
+                (pos.z >= center.z) as i32,
+            );
+            let child_idx = (gt.x as u32) | (gt.y as u32) << 1 | (gt.z as u32) << 2;
+            node_idx = self.get_node(node_idx).get_child(child_idx);
+            let child_dir = gt * 2 - IVec3::ONE;
+            center += IVec3::splat(size as i32 / 2) * child_dir;
+        }
+
+        // A parent can only collapse if the one below it did.
+        for idx in parents.into_iter().rev() {
+            let Some(voxel) = self.can_simplify(idx) else {
+                break;
+            };
+            self.free_nodes(self.get_node(idx).first_child());
+            self.mut_node(idx).simplify(voxel);
+            result.push(NodeSeq { idx, count: 1 });
+        }
+    }
+
+    /// Returns the voxel the node at `idx` can collapse to,
+    /// if it is split and all of its 8 children are leaves of the same voxel.
+    pub fn can_simplify(&self, idx: u32) -> Option<Voxel> {
+        let node = self.get_node(idx);
+        if !node.is_split() {
+            return None;
+        }
+        let voxel = self.get_node(node.first_child()).get_voxel();
+        (0..8)
+            .map(|i| self.get_node(node.get_child(i)))
+            .all(|child| !child.is_split() && child.get_voxel() == voxel)
+            .then_some(voxel)
+    }
+
+    /// The height right above the highest voxel of the column that isn't air, water included,
+    /// where nothing but air is left up to the top of the world.
+    ///
+    /// Fails if the column is outside the world or filled up to the top.
+    pub fn surface_at(&self, x: i32, z: i32) -> Result<i32, WorldErr> {
+        for y in (self.min.y..self.max().y).rev() {
+            if self.get_voxel(ivec3(x, y, z))? != Voxel::AIR {
+                return (y + 1 < self.max().y)
+                    .then_some(y + 1)
+                    .ok_or(WorldErr::OutOfBounds);
+            }
+        }
+        Ok(self.min.y)
+    }
+
+    pub fn get_collisions_w(&self, aabb: &Aabb) -> Vec<Aabb> {
+        let mut aabbs = Vec::new();
+
+        let from = aabb.from.floor().as_ivec3();
+        let to = aabb.to.ceil().as_ivec3();
+
+        for x in from.x..to.x {
+            for y in from.y..to.y {
+                for z in from.z..to.z {
+                    let pos = ivec3(x, y, z);
+
+                    let voxel = self.get_voxel(pos).unwrap_or(Voxel::AIR);
+
+                    if !voxel.is_empty() {
+                        let min = pos.as_vec3();
+                        let max = min + 1.0;
+                        aabbs.push(Aabb::new(min, max));
+                    }
+                }
+            }
+        }
+        aabbs
+    }
+
+    /// Fills a sphere where every voxel has a `decay` chance to be skipped, the skipped voxels
+    /// only depend on their position and one value drawn from `rng`.
+    pub fn sphere(
+        &mut self,
+        pos: IVec3,
+        r: u32,
+        voxel: Voxel,
+        decay: f32,
+        rng: &mut Rng,
+    ) -> Result<Vec<NodeSeq>, WorldErr> {
+        let salt = rng.u64(..);
+        self.fill_shape(voxel, |node_min, size| {
+            Coverage::of_decayed_sphere(node_min, size, pos, r, decay, salt)
+        })
+    }
+
+    pub fn bounded_sphere(
+        &mut self,
+        pos: IVec3,
+        r: u32,
+        voxel: Voxel,
+        min: IVec3,
+        max: IVec3,
+    ) -> Result<Vec<NodeSeq>, WorldErr> {
+        self.fill_shape(voxel, |node_min, size| {
+            Coverage::of_box(node_min, size, min, max)
+                .min(Coverage::of_sphere(node_min, size, pos, r))
+        })
+    }
+}
+
+//= TESTS ==========================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dirty_ranges_cover_the_modified_nodes() {
+        let mut world = World::new_region(IVec3::ZERO, 6);
+        let mut rng = Rng::with_seed(3);
+
+        for _ in 0..20 {
+            drop(world.dirty_ranges().take());
+            let before = world.nodes().to_vec();
+
+            let mut seqs = vec![];
+            for _ in 0..rng.usize(1..30) {
+                let pos = ivec3(rng.i32(0..64), rng.i32(0..64), rng.i32(0..64));
+                let voxel = Voxel(rng.u16(0..4));
+                match rng.u8(0..3) {
+                    0 => seqs.extend(world.set_voxel(pos, voxel).unwrap()),
+                    1 => seqs.extend(world.fill_box(pos, pos + rng.i32(1..16), voxel).unwrap()),
+                    _ => seqs.extend(
+                        world
+                            .sphere(pos, rng.u32(1..8), voxel, 0.0, &mut rng)
+                            .unwrap(),
+                    ),
+                }
+            }
+
+            let ranges = world.dirty_ranges().take();
+            let is_dirty = |idx: u32| ranges.iter().any(|range| range.contains(&idx));
+            assert!(ranges.windows(2).all(|pair| pair[0].end < pair[1].start));
+            for (idx, node) in world.nodes().iter().enumerate() {
+                let old = before.get(idx).map_or(Node::ZERO.raw(), |node| node.raw());
+                assert!(
+                    old == node.raw() || is_dirty(idx as u32),
+                    "node {idx} not dirty"
+                );
+            }
+            for seq in seqs {
+                assert!((seq.idx..seq.idx + u32::from(seq.count)).all(is_dirty));
+            }
+        }
+    }
+
+    #[test]
+    fn surface_is_above_water() {
+        let mut world = World::new_region(IVec3::ZERO, 6);
+        world
+            .fill_box(IVec3::ZERO, ivec3(63, 9, 63), Voxel::STONE)
+            .unwrap();
+        world
+            .fill_box(ivec3(0, 10, 0), ivec3(63, 19, 63), Voxel::WATER)
+            .unwrap();
+        assert_eq!(world.surface_at(5, 5).unwrap(), 20);
+
+        world
+            .fill_box(ivec3(5, 20, 5), ivec3(5, 63, 5), Voxel::STONE)
+            .unwrap();
+        assert!(matches!(world.surface_at(5, 5), Err(WorldErr::OutOfBounds)));
+    }
+}
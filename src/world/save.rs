@@ -0,0 +1,313 @@
+//= IMPORTS ========================================================================================
+
+use crate::world::{World, WorldErr};
+
+use voxel_render::{Node, VOXEL_MATERIALS, Voxel};
+
+use glam::IVec3;
+
+use std::fs::{read, write};
+
+//= CONSTANTS ======================================================================================
+
+const MAGIC: [u8; 4] = *b"VRTW";
+
+/// Bump it every time the layout below changes, `World::load` converts the old files
+/// it still knows how to read.
+pub const WORLD_FORMAT_VERSION: u16 = 2;
+
+// magic + version + reserved + seed + min + size + max_depth + node count
+const HEADER_LEN: usize = 4 + 2 + 2 + 8 + 3 * 4 + 4 + 4 + 4;
+const CHECKSUM_LEN: usize = 8;
+
+//= SAVE AND LOAD ==================================================================================
+
+/// Persist worlds on disk.
+///
+/// ## File Layout
+/// All the values are little endian:
+/// ```text
+/// magic        [u8; 4]  "VRTW"
+/// version      u16      WORLD_FORMAT_VERSION
+/// reserved     u16      always zero
+/// seed         i64
+/// min          [i32; 3]
+/// size         u32
+/// max_depth    u32
+/// node_count   u32
+/// nodes        [u32; node_count]
+/// checksum     u64      FNV-1a of all the previous bytes
+/// ```
+/// Only the nodes reachable from the root are written, depth first,
+/// so the free holes of the pool are never saved and the subtrees shared by
+/// `World::deduplicate` are saved once. The children of a split node always come after it,
+/// and `World::load` rejects the files where they don't.
+///
+/// Version 1 files have the same layout, but their leaves only use the lowest 8 bits
+/// for the voxel.
+impl World {
+    pub fn save(&self, filename: &str) -> Result<(), WorldErr> {
+        let (nodes, _) = self.compacted_nodes();
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + nodes.len() * 4 + CHECKSUM_LEN);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&WORLD_FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        for c in self.min.to_array() {
+            bytes.extend_from_slice(&c.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.size.to_le_bytes());
+        bytes.extend_from_slice(&self.max_depth.to_le_bytes());
+        bytes.extend_from_slice(&(nodes.len() as u32).to_le_bytes());
+        for node in &nodes {
+            bytes.extend_from_slice(&node.raw().to_le_bytes());
+        }
+        bytes.extend_from_slice(&fnv1a(&bytes).to_le_bytes());
+
+        write(filename, bytes).map_err(|e| WorldErr::Io(format!("{e}: {filename}")))
+    }
+
+    pub fn load(filename: &str, max_buffer_sizes: u64) -> Result<Self, WorldErr> {
+        let bytes = read(filename).map_err(|e| WorldErr::Io(format!("{e}: {filename}")))?;
+        if bytes.len() < HEADER_LEN + CHECKSUM_LEN || bytes[0..4] != MAGIC {
+            return Err(WorldErr::InvalidFormat);
+        }
+
+        let (payload, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+        let mut reader = Reader(payload);
+        reader.take::<4>();
+
+        let version = u16::from_le_bytes(reader.take());
+        if !(1..=WORLD_FORMAT_VERSION).contains(&version) {
+            return Err(WorldErr::UnsupportedVersion(version));
+        }
+        if u64::from_le_bytes(checksum.try_into().unwrap()) != fnv1a(payload) {
+            return Err(WorldErr::ChecksumMismatch);
+        }
+        reader.take::<2>();
+
+        let seed = i64::from_le_bytes(reader.take());
+        let min = IVec3::new(
+            i32::from_le_bytes(reader.take()),
+            i32::from_le_bytes(reader.take()),
+            i32::from_le_bytes(reader.take()),
+        );
+        let size = u32::from_le_bytes(reader.take());
+        let max_depth = u32::from_le_bytes(reader.take());
+        let node_count = u32::from_le_bytes(reader.take()) as usize;
+
+        if node_count == 0
+            || reader.0.len() != node_count * 4
+            || Some(size) != 1u32.checked_shl(max_depth)
+        {
+            return Err(WorldErr::InvalidFormat);
+        }
+
+        let mut world = Self::new(max_buffer_sizes, max_depth)?;
+        if node_count as u64 > world.max_nodes {
+            return Err(WorldErr::OutOfNodes);
+        }
+        for idx in 0..node_count {
+            let node = migrate_node(version, Node::from_raw(u32::from_le_bytes(reader.take())));
+            if !node.is_used() || !is_valid_node(node, idx, node_count) {
+                return Err(WorldErr::InvalidFormat);
+            }
+            world.nodes[idx] = node;
+        }
+
+        world.seed = seed;
+        world.min = min;
+        world.start_search = node_count as u32;
+        world.last_used_node = node_count as u32 - 1;
+        world.dirty.mark(0, node_count as u32);
+        world.recount_refs();
+        Ok(world)
+    }
+}
+
+//= HELPERS ========================================================================================
+
+/// Whether the node at `idx` of a file with `node_count` nodes is a known voxel, or points
+/// to a whole group of children after it, so the file can't hold cycles.
+fn is_valid_node(node: Node, idx: usize, node_count: usize) -> bool {
+    if !node.is_split() {
+        return usize::from(node.get_voxel().0) < VOXEL_MATERIALS.len();
+    }
+    let first_child = node.first_child() as usize;
+    first_child > idx && (first_child - 1).is_multiple_of(8) && first_child + 8 <= node_count
+}
+
+/// Converts a node saved with an older format version to the current one.
+fn migrate_node(version: u16, mut node: Node) -> Node {
+    if version == 1 && node.is_used() && !node.is_split() {
+        // The bits above the first 8 were unused, now they're part of the voxel.
+        node.set_voxel(Voxel(node.get_voxel().0 & 0xff));
+    }
+    node
+}
+
+pub(crate) struct Reader<'a>(pub(crate) &'a [u8]);
+
+impl Reader<'_> {
+    pub(crate) fn take<const N: usize>(&mut self) -> [u8; N] {
+        let (head, tail) = self.0.split_at(N);
+        self.0 = tail;
+        head.try_into().unwrap()
+    }
+}
+
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    bytes.iter().fold(OFFSET_BASIS, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(PRIME)
+    })
+}
+
+//= TESTS ==========================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{BiomeRegistry, WorldGen};
+
+    use glam::ivec3;
+
+    use std::env::temp_dir;
+
+    const MAX_BUFFER_SIZES: u64 = 1 << 24;
+
+    fn generated_world() -> World {
+        let biomes = BiomeRegistry::from_config(&Default::default()).unwrap();
+        let mut world = World::new(MAX_BUFFER_SIZES, 7).unwrap();
+        world.seed = 5;
+        WorldGen::new(5, biomes)
+            .populate(world.min(), world.max(), &mut world)
+            .unwrap();
+        world
+    }
+
+    /// Saves `world` and returns the bytes of the file.
+    fn saved_bytes(world: &World, name: &str) -> Vec<u8> {
+        let filepath = temp_dir().join(name);
+        world.save(filepath.to_str().unwrap()).unwrap();
+        let bytes = read(&filepath).unwrap();
+        std::fs::remove_file(&filepath).unwrap();
+        bytes
+    }
+
+    /// Loads a world file made of `bytes`, with the checksum recomputed if `fix_checksum`.
+    fn load_bytes(mut bytes: Vec<u8>, name: &str, fix_checksum: bool) -> Result<World, WorldErr> {
+        if fix_checksum {
+            let payload_len = bytes.len() - CHECKSUM_LEN;
+            let checksum = fnv1a(&bytes[..payload_len]);
+            bytes[payload_len..].copy_from_slice(&checksum.to_le_bytes());
+        }
+        let filepath = temp_dir().join(name);
+        write(&filepath, bytes).unwrap();
+        let world = World::load(filepath.to_str().unwrap(), MAX_BUFFER_SIZES);
+        std::fs::remove_file(&filepath).unwrap();
+        world
+    }
+
+    fn assert_same_voxels(a: &World, b: &World) {
+        assert_eq!(a.min(), b.min());
+        assert_eq!(a.size, b.size);
+        for x in 0..a.size as i32 {
+            for y in 0..a.size as i32 {
+                for z in 0..a.size as i32 {
+                    let pos = a.min() + ivec3(x, y, z);
+                    assert!(
+                        a.get_voxel(pos).unwrap() == b.get_voxel(pos).unwrap(),
+                        "{pos}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn generated_world_round_trips() {
+        let mut world = generated_world();
+        let bytes = saved_bytes(&world, "vrt_round_trip.vrtw");
+        let loaded = load_bytes(bytes, "vrt_round_trip.vrtw", false).unwrap();
+        assert_eq!(loaded.seed, world.seed);
+        assert_same_voxels(&world, &loaded);
+
+        // The shared subtrees are saved once and still load.
+        world.deduplicate();
+        let bytes = saved_bytes(&world, "vrt_round_trip_dag.vrtw");
+        let loaded = load_bytes(bytes, "vrt_round_trip_dag.vrtw", false).unwrap();
+        assert_same_voxels(&world, &loaded);
+    }
+
+    #[test]
+    fn corrupted_files_are_rejected() {
+        let mut world = World::new(MAX_BUFFER_SIZES, 7).unwrap();
+        world.set_voxel(IVec3::ZERO, Voxel::STONE).unwrap();
+        let bytes = saved_bytes(&world, "vrt_corrupted.vrtw");
+        let node_offset = |idx: usize| HEADER_LEN + idx * 4;
+
+        let mut flipped = bytes.clone();
+        flipped[HEADER_LEN - 1] ^= 1;
+        let loaded = load_bytes(flipped, "vrt_corrupted.vrtw", false);
+        assert!(matches!(loaded, Err(WorldErr::ChecksumMismatch)));
+
+        let mut future = bytes.clone();
+        future[4..6].copy_from_slice(&(WORLD_FORMAT_VERSION + 1).to_le_bytes());
+        let loaded = load_bytes(future, "vrt_corrupted.vrtw", true);
+        assert!(
+            matches!(loaded, Err(WorldErr::UnsupportedVersion(v)) if v == WORLD_FORMAT_VERSION + 1)
+        );
+
+        // The first child of the root points back to the group holding it.
+        let mut cyclic = bytes.clone();
+        assert!(
+            Node::from_raw(u32::from_le_bytes(
+                cyclic[node_offset(1)..][..4].try_into().unwrap()
+            ))
+            .is_split()
+        );
+        cyclic[node_offset(1)..][..4].copy_from_slice(&Node::new_split(1).raw().to_le_bytes());
+        let loaded = load_bytes(cyclic, "vrt_corrupted.vrtw", true);
+        assert!(matches!(loaded, Err(WorldErr::InvalidFormat)));
+
+        let mut unknown = bytes;
+        let leaf = Node::new_leaf(Voxel(VOXEL_MATERIALS.len() as u16));
+        unknown[node_offset(2)..][..4].copy_from_slice(&leaf.raw().to_le_bytes());
+        let loaded = load_bytes(unknown, "vrt_corrupted.vrtw", true);
+        assert!(matches!(loaded, Err(WorldErr::InvalidFormat)));
+    }
+
+    #[test]
+    fn version_1_files_are_migrated() {
+        // A world of depth 7 split once, saved before the voxels were widened to 16 bits,
+        // the unused bits above the 8 of every voxel are set.
+        #[rustfmt::skip]
+        let mut bytes = vec![
+            b'V', b'R', b'T', b'W', // magic
+            1, 0, 0, 0,             // version, reserved
+            9, 0, 0, 0, 0, 0, 0, 0, // seed
+            0xfe, 0xff, 0xff, 0xff, 64, 0, 0, 0, 0, 1, 0, 0, // min
+            128, 0, 0, 0,           // size
+            7, 0, 0, 0,             // max_depth
+            9, 0, 0, 0,             // node_count
+            0, 0, 0, 0xc0,          // root, split with its children at 1
+        ];
+        for voxel in 1..=8 {
+            bytes.extend_from_slice(&[voxel as u8, 0xab, 0, 0x40]);
+        }
+        bytes.extend_from_slice(&[0; CHECKSUM_LEN]);
+
+        let world = load_bytes(bytes, "vrt_version_1.vrtw", true).unwrap();
+        assert_eq!(world.seed, 9);
+        assert_eq!(world.min(), ivec3(-2, 64, 256));
+        assert_eq!(world.nodes()[0].raw(), Node::new_split(1).raw());
+        for voxel in 1..=8u16 {
+            let node = world.nodes()[usize::from(voxel)];
+            assert_eq!(node.raw(), Node::new_leaf(Voxel(voxel)).raw());
+        }
+    }
+}
@@ -0,0 +1,417 @@
+//= IMPORTS ========================================================================================
+
+use crate::world::{NodeSeq, Reader, World, WorldErr, coalesce_node_seqs, fnv1a};
+
+use voxel_render::{VOXEL_NAMES, Voxel};
+
+use glam::{IVec3, ivec3};
+
+use std::fs::{read, write};
+
+//= CONSTANTS ======================================================================================
+
+const MAGIC: [u8; 4] = *b"VRTS";
+
+/// Bump it every time the layout below changes, `Schematic::load` converts the old files
+/// it still knows how to read.
+pub const SCHEMATIC_FORMAT_VERSION: u16 = 2;
+
+// magic + version + reserved + size + run count
+const HEADER_LEN: usize = 4 + 2 + 2 + 3 * 4 + 4;
+const CHECKSUM_LEN: usize = 8;
+
+//= SCHEMATIC ======================================================================================
+
+/// The axis flipped by `Schematic::mirrored`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+/// How `Schematic::paste` treats the air voxels of the schematic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PasteMode {
+    /// Every voxel of the world inside the schematic box is replaced.
+    Replace,
+    /// The air voxels of the schematic leave the world untouched.
+    IgnoreAir,
+}
+
+/// A box of voxels copied out of a world, that can be pasted elsewhere.
+#[derive(Clone)]
+pub struct Schematic {
+    size: IVec3,
+    /// Indexed by `x + (z + y * size.z) * size.x`.
+    voxels: Vec<Voxel>,
+}
+
+impl Schematic {
+    /// Copies the box going from `a` to `b`, both inclusive and in any order.
+    pub fn capture(world: &World, a: IVec3, b: IVec3) -> Result<Self, WorldErr> {
+        world.check_bounds(a)?;
+        world.check_bounds(b)?;
+        let min = a.min(b);
+        let max = a.max(b) + IVec3::ONE;
+
+        let mut result = Self::filled(max - min, Voxel::AIR);
+        world.visit_region(min, max, |leaf| {
+            if leaf.voxel == Voxel::AIR {
+                return;
+            }
+            let (from, to) = (leaf.min.max(min) - min, leaf.max().min(max) - min);
+            for y in from.y..to.y {
+                for z in from.z..to.z {
+                    let start = result.index(ivec3(from.x, y, z));
+                    result.voxels[start..start + (to.x - from.x) as usize].fill(leaf.voxel);
+                }
+            }
+        });
+        Ok(result)
+    }
+
+    /// A schematic of `size` with every voxel set to `voxel`.
+    #[must_use]
+    pub fn filled(size: IVec3, voxel: Voxel) -> Self {
+        Self {
+            size,
+            voxels: vec![voxel; (size.x * size.y * size.z) as usize],
+        }
+    }
+
+    #[must_use]
+    pub const fn size(&self) -> IVec3 {
+        self.size
+    }
+
+    /// The voxel at `pos`, relative to the min corner of the schematic.
+    #[must_use]
+    pub fn get(&self, pos: IVec3) -> Voxel {
+        self.voxels[self.index(pos)]
+    }
+
+    /// Sets the voxel at `pos`, relative to the min corner of the schematic.
+    pub fn set(&mut self, pos: IVec3, voxel: Voxel) {
+        let idx = self.index(pos);
+        self.voxels[idx] = voxel;
+    }
+
+    /// The voxels with their position relative to the min corner of the schematic.
+    pub fn voxels(&self) -> impl Iterator<Item = (IVec3, Voxel)> + '_ {
+        positions(self.size).zip(self.voxels.iter().copied())
+    }
+
+    const fn index(&self, pos: IVec3) -> usize {
+        (pos.x + (pos.z + pos.y * self.size.z) * self.size.x) as usize
+    }
+
+    //- Transformations ----------------------------------------------------------------------------
+
+    /// Rotates the schematic around the Y axis by `turns` quarter turns, clockwise seen
+    /// from above, negative turns go counterclockwise.
+    #[must_use]
+    pub fn rotated(&self, turns: i32) -> Self {
+        let mut result = self.clone();
+        for _ in 0..turns.rem_euclid(4) {
+            let size = ivec3(result.size.z, result.size.y, result.size.x);
+            let voxels = positions(size)
+                .map(|pos| result.get(ivec3(pos.z, pos.y, size.x - 1 - pos.x)))
+                .collect();
+            result = Self { size, voxels };
+        }
+        result
+    }
+
+    /// Flips the schematic along `axis`.
+    #[must_use]
+    pub fn mirrored(&self, axis: Axis) -> Self {
+        let size = self.size;
+        let voxels = positions(size)
+            .map(|pos| {
+                self.get(match axis {
+                    Axis::X => pos.with_x(size.x - 1 - pos.x),
+                    Axis::Y => pos.with_y(size.y - 1 - pos.y),
+                    Axis::Z => pos.with_z(size.z - 1 - pos.z),
+                })
+            })
+            .collect();
+        Self { size, voxels }
+    }
+
+    //- Paste --------------------------------------------------------------------------------------
+
+    /// Pastes the schematic with its min corner at `pos`, the voxels outside the world are
+    /// left out. Returns the modified nodes.
+    ///
+    /// Fails if the world runs out of nodes, the voxels pasted until then stay pasted.
+    pub fn paste(
+        &self,
+        world: &mut World,
+        pos: IVec3,
+        mode: PasteMode,
+    ) -> Result<Vec<NodeSeq>, WorldErr> {
+        let mut result = vec![];
+        for (offset, voxel) in self.voxels() {
+            if mode == PasteMode::IgnoreAir && voxel == Voxel::AIR {
+                continue;
+            }
+            match world.set_voxel(pos + offset, voxel) {
+                Ok(seqs) => result.extend(seqs),
+                Err(WorldErr::OutOfBounds) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(coalesce_node_seqs(result))
+    }
+}
+
+/// Persist schematics on disk.
+///
+/// ## File Layout
+/// All the values are little endian:
+/// ```text
+/// magic        [u8; 4]  "VRTS"
+/// version      u16      SCHEMATIC_FORMAT_VERSION
+/// reserved     u16      always zero
+/// size         [u32; 3]
+/// run_count    u32
+/// runs         [(u16, u16); run_count]  voxel and length, in the order of `Schematic::voxels`
+/// checksum     u64      FNV-1a of all the previous bytes
+/// ```
+/// Version 1 files store the voxels of the runs in a single byte. Unknown voxels are rejected.
+impl Schematic {
+    pub fn save(&self, filename: &str) -> Result<(), WorldErr> {
+        let mut runs: Vec<(Voxel, u16)> = vec![];
+        for &voxel in &self.voxels {
+            match runs.last_mut() {
+                Some((last, len)) if *last == voxel && *len < u16::MAX => *len += 1,
+                _ => runs.push((voxel, 1)),
+            }
+        }
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + runs.len() * 4 + CHECKSUM_LEN);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&SCHEMATIC_FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        for c in self.size.to_array() {
+            bytes.extend_from_slice(&(c as u32).to_le_bytes());
+        }
+        bytes.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+        for (voxel, len) in runs {
+            bytes.extend_from_slice(&voxel.0.to_le_bytes());
+            bytes.extend_from_slice(&len.to_le_bytes());
+        }
+        bytes.extend_from_slice(&fnv1a(&bytes).to_le_bytes());
+
+        write(filename, bytes).map_err(|e| WorldErr::Io(format!("{e}: {filename}")))
+    }
+
+    pub fn load(filename: &str) -> Result<Self, WorldErr> {
+        let bytes = read(filename).map_err(|e| WorldErr::Io(format!("{e}: {filename}")))?;
+        if bytes.len() < HEADER_LEN + CHECKSUM_LEN || bytes[0..4] != MAGIC {
+            return Err(WorldErr::InvalidFormat);
+        }
+
+        let (payload, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+        let mut reader = Reader(payload);
+        reader.take::<4>();
+
+        let version = u16::from_le_bytes(reader.take());
+        if !(1..=SCHEMATIC_FORMAT_VERSION).contains(&version) {
+            return Err(WorldErr::UnsupportedVersion(version));
+        }
+        if u64::from_le_bytes(checksum.try_into().unwrap()) != fnv1a(payload) {
+            return Err(WorldErr::ChecksumMismatch);
+        }
+        reader.take::<2>();
+
+        let mut size = [0; 3];
+        for c in &mut size {
+            *c = u32::from_le_bytes(reader.take());
+        }
+        let volume = size
+            .iter()
+            .try_fold(1u32, |volume, &c| volume.checked_mul(c))
+            .filter(|&volume| volume > 0 && volume <= i32::MAX as u32)
+            .ok_or(WorldErr::InvalidFormat)? as usize;
+        let run_count = u32::from_le_bytes(reader.take()) as usize;
+        let run_len = if version == 1 { 3 } else { 4 };
+        if reader.0.len() != run_count * run_len {
+            return Err(WorldErr::InvalidFormat);
+        }
+
+        let mut voxels = Vec::with_capacity(volume.min(run_count * u16::MAX as usize));
+        for _ in 0..run_count {
+            let voxel = if version == 1 {
+                u16::from(reader.take::<1>()[0])
+            } else {
+                u16::from_le_bytes(reader.take())
+            };
+            if usize::from(voxel) >= VOXEL_NAMES.len() {
+                return Err(WorldErr::InvalidFormat);
+            }
+            let len = u16::from_le_bytes(reader.take());
+            voxels.extend(std::iter::repeat_n(Voxel(voxel), len as usize));
+        }
+        if voxels.len() != volume {
+            return Err(WorldErr::InvalidFormat);
+        }
+
+        Ok(Self {
+            size: IVec3::from_array(size.map(|c| c as i32)),
+            voxels,
+        })
+    }
+}
+
+//= HELPERS ========================================================================================
+
+/// The positions inside a box of `size` from the origin, in the order of `Schematic::voxels`.
+fn positions(size: IVec3) -> impl Iterator<Item = IVec3> {
+    (0..size.y)
+        .flat_map(move |y| (0..size.z).flat_map(move |z| (0..size.x).map(move |x| ivec3(x, y, z))))
+}
+
+//= TESTS ==========================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capture_keeps_water() {
+        let mut world = World::new_region(IVec3::ZERO, 6);
+        world
+            .fill_box(ivec3(4, 4, 4), ivec3(19, 11, 19), Voxel::WATER)
+            .unwrap();
+        world
+            .fill_box(ivec3(8, 0, 8), ivec3(11, 5, 11), Voxel::STONE)
+            .unwrap();
+
+        let (a, b) = (ivec3(2, 0, 3), ivec3(21, 13, 20));
+        let schematic = Schematic::capture(&world, a, b).unwrap();
+        assert!(schematic.get(ivec3(4, 4, 4) - a) == Voxel::WATER);
+        for (offset, voxel) in schematic.voxels() {
+            let pos = a + offset;
+            assert!(world.get_voxel(pos).unwrap() == voxel, "{pos}");
+        }
+    }
+
+    #[test]
+    fn paste_ignoring_air_keeps_water() {
+        let mut world = World::new_region(IVec3::ZERO, 6);
+        Schematic::filled(IVec3::splat(3), Voxel::WATER)
+            .paste(&mut world, ivec3(5, 6, 7), PasteMode::IgnoreAir)
+            .unwrap();
+        assert!(world.get_voxel(ivec3(6, 7, 8)).unwrap() == Voxel::WATER);
+    }
+
+    #[test]
+    fn unknown_voxels_are_rejected() {
+        let filepath = std::env::temp_dir().join("vrt_unknown_voxel.vrts");
+        let filename = filepath.to_str().unwrap();
+        let mut schematic = Schematic::filled(IVec3::ONE * 2, Voxel::STONE);
+        schematic.set(IVec3::ONE, Voxel(VOXEL_NAMES.len() as u16));
+        schematic.save(filename).unwrap();
+
+        let loaded = Schematic::load(filename);
+        std::fs::remove_file(&filepath).unwrap();
+        assert!(matches!(loaded, Err(WorldErr::InvalidFormat)));
+    }
+
+    /// A schematic with a different voxel at every position, so any misplaced voxel shows.
+    fn asymmetric() -> Schematic {
+        let mut schematic = Schematic::filled(ivec3(2, 3, 4), Voxel::AIR);
+        for (idx, pos) in positions(schematic.size()).enumerate() {
+            schematic.set(pos, Voxel(idx as u16 + 1));
+        }
+        schematic
+    }
+
+    fn assert_same(a: &Schematic, b: &Schematic) {
+        assert_eq!(a.size(), b.size());
+        for ((pos, a), (_, b)) in a.voxels().zip(b.voxels()) {
+            assert!(a == b, "{pos}");
+        }
+    }
+
+    #[test]
+    fn quarter_turns_move_every_voxel() {
+        let schematic = asymmetric();
+        let size = schematic.size();
+        let rotated = schematic.rotated(1);
+        assert_eq!(rotated.size(), ivec3(4, 3, 2));
+        // Clockwise seen from above, +X goes to +Z.
+        for (pos, voxel) in schematic.voxels() {
+            let turned = ivec3(size.z - 1 - pos.z, pos.y, pos.x);
+            assert!(rotated.get(turned) == voxel, "{pos}");
+        }
+
+        assert_same(&schematic.rotated(4), &schematic);
+        assert_same(&schematic.rotated(-1), &schematic.rotated(3));
+        let mut turned = schematic.clone();
+        for _ in 0..4 {
+            turned = turned.rotated(1);
+        }
+        assert_same(&turned, &schematic);
+    }
+
+    #[test]
+    fn mirroring_twice_is_the_identity() {
+        let schematic = asymmetric();
+        let size = schematic.size();
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            let mirrored = schematic.mirrored(axis);
+            let corner = mirrored.get(IVec3::ZERO);
+            let opposite = match axis {
+                Axis::X => ivec3(size.x - 1, 0, 0),
+                Axis::Y => ivec3(0, size.y - 1, 0),
+                Axis::Z => ivec3(0, 0, size.z - 1),
+            };
+            assert!(corner == schematic.get(opposite), "{axis:?}");
+            assert_same(&mirrored.mirrored(axis), &schematic);
+        }
+    }
+
+    #[test]
+    fn saved_schematic_round_trips() {
+        let filepath = std::env::temp_dir().join("vrt_round_trip.vrts");
+        let filename = filepath.to_str().unwrap();
+        let mut schematic = asymmetric().rotated(1);
+        schematic.set(IVec3::ZERO, Voxel::AIR);
+        schematic.set(IVec3::ONE, Voxel::AIR);
+        schematic.save(filename).unwrap();
+
+        let loaded = Schematic::load(filename);
+        std::fs::remove_file(&filepath).unwrap();
+        assert_same(&loaded.unwrap(), &schematic);
+    }
+
+    #[test]
+    fn version_1_files_are_read() {
+        // A 2x1x2 schematic saved when the voxels were a byte: two air then two voxels 5.
+        #[rustfmt::skip]
+        let mut bytes = vec![
+            b'V', b'R', b'T', b'S', // magic
+            1, 0, 0, 0,             // version, reserved
+            2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, // size
+            2, 0, 0, 0,             // run count
+            0, 2, 0,                // air, length 2
+            5, 2, 0,                // voxel 5, length 2
+        ];
+        bytes.extend_from_slice(&fnv1a(&bytes).to_le_bytes());
+
+        let filepath = std::env::temp_dir().join("vrt_version_1.vrts");
+        std::fs::write(&filepath, bytes).unwrap();
+        let loaded = Schematic::load(filepath.to_str().unwrap());
+        std::fs::remove_file(&filepath).unwrap();
+
+        let schematic = loaded.unwrap();
+        assert_eq!(schematic.size(), ivec3(2, 1, 2));
+        for (pos, voxel) in schematic.voxels() {
+            let expected = if pos.z == 0 { Voxel::AIR } else { Voxel(5) };
+            assert!(voxel == expected, "{pos}");
+        }
+    }
+}
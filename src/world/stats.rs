@@ -0,0 +1,138 @@
+//= IMPORTS ========================================================================================
+
+use crate::world::World;
+
+use voxel_render::{Node, Voxel};
+
+use hashbrown::HashMap;
+
+use std::fmt;
+
+//= CONSTANTS ======================================================================================
+
+/// Above this ratio of `World::max_nodes` used, `WorldStats` reports that the budget is
+/// running out, past it the edits fail with `WorldErr::OutOfNodes`.
+pub const NODES_BUDGET_WARNING: f32 = 0.9;
+
+//= WORLD STATS ====================================================================================
+
+/// A report of how the world uses its pool of nodes, see `World::stats`.
+#[derive(Clone, Default)]
+pub struct WorldStats {
+    /// Nodes in the range returned by `World::nodes`, the only ones uploaded to the GPU.
+    pub uploaded_nodes: u32,
+    pub used_nodes: u32,
+    /// Holes left by freed nodes inside the uploaded range.
+    pub free_nodes: u32,
+    pub split_nodes: u32,
+    pub leaf_nodes: u32,
+    /// Nodes the GPU buffer can hold.
+    pub max_nodes: u64,
+    /// Leaves of the tree for each depth, from the root.
+    pub depth_histogram: Vec<u64>,
+    /// Voxels of each type, sorted by type.
+    pub volumes: Vec<(Voxel, u64)>,
+}
+
+impl WorldStats {
+    /// Size in bytes of the nodes uploaded to the GPU.
+    #[must_use]
+    pub fn uploaded_bytes(&self) -> u64 {
+        u64::from(self.uploaded_nodes) * size_of::<Node>() as u64
+    }
+
+    /// Ratio of the free nodes inside the uploaded range, see `World::fragmentation`.
+    #[must_use]
+    pub fn fragmentation(&self) -> f32 {
+        self.free_nodes as f32 / self.uploaded_nodes.max(1) as f32
+    }
+
+    /// Ratio of `max_nodes` taken by the uploaded nodes.
+    #[must_use]
+    pub fn budget_used(&self) -> f32 {
+        self.uploaded_nodes as f32 / self.max_nodes.max(1) as f32
+    }
+
+    /// The deepest level holding a leaf.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.depth_histogram.len().saturating_sub(1)
+    }
+}
+
+/// Multi-line report, meant for the log.
+impl fmt::Display for WorldStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "nodes: {} uploaded ({} KiB), {} used, {} free, {} split, {} leaves",
+            self.uploaded_nodes,
+            self.uploaded_bytes() / 1024,
+            self.used_nodes,
+            self.free_nodes,
+            self.split_nodes,
+            self.leaf_nodes,
+        )?;
+        writeln!(
+            f,
+            "budget: {:.1}% of {} nodes, fragmentation {:.1}%",
+            self.budget_used() * 100.0,
+            self.max_nodes,
+            self.fragmentation() * 100.0,
+        )?;
+        if self.budget_used() > NODES_BUDGET_WARNING {
+            writeln!(f, "budget almost exhausted, edits fail once it runs out")?;
+        }
+        write!(f, "leaves per depth:")?;
+        for (depth, count) in self.depth_histogram.iter().enumerate() {
+            write!(f, " {depth}:{count}")?;
+        }
+        writeln!(f)?;
+        write!(f, "volume per voxel:")?;
+        for (voxel, volume) in &self.volumes {
+            write!(f, " {}:{volume}", voxel.0)?;
+        }
+        Ok(())
+    }
+}
+
+//= STATS ==========================================================================================
+
+impl World {
+    /// Counts the nodes of the pool and walks the leaves of the tree.
+    ///
+    /// Nodes shared by `World::deduplicate` are counted once in the node counts,
+    /// and once for every place they appear in the histogram and volumes.
+    #[must_use]
+    pub fn stats(&self) -> WorldStats {
+        let mut result = WorldStats {
+            uploaded_nodes: self.nodes().len() as u32,
+            max_nodes: self.max_nodes,
+            ..WorldStats::default()
+        };
+        for node in self.nodes() {
+            match (node.is_used(), node.is_split()) {
+                (false, _) => result.free_nodes += 1,
+                (true, true) => result.split_nodes += 1,
+                (true, false) => result.leaf_nodes += 1,
+            }
+        }
+        result.used_nodes = result.split_nodes + result.leaf_nodes;
+
+        let mut volumes = HashMap::<u16, u64>::new();
+        for leaf in self.leaves() {
+            let depth = (self.size / leaf.size).trailing_zeros() as usize;
+            if depth >= result.depth_histogram.len() {
+                result.depth_histogram.resize(depth + 1, 0);
+            }
+            result.depth_histogram[depth] += 1;
+            *volumes.entry(leaf.voxel.0).or_default() += u64::from(leaf.size).pow(3);
+        }
+        result.volumes = volumes
+            .into_iter()
+            .map(|(voxel, volume)| (Voxel(voxel), volume))
+            .collect();
+        result.volumes.sort_unstable_by_key(|(voxel, _)| voxel.0);
+        result
+    }
+}
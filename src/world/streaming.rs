@@ -0,0 +1,259 @@
+//= IMPORTS ========================================================================================
+
+use crate::world::{CHUNK_SIZE, World, WorldErr, WorldGen};
+
+use glam::{IVec3, Vec3, ivec3};
+use hashbrown::{HashMap, HashSet};
+
+use std::collections::VecDeque;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+//= CONSTANTS ======================================================================================
+
+/// Time spent grafting chunks into the world every frame by default.
+pub const STREAMING_BUDGET: Duration = Duration::from_millis(4);
+
+//= WORLD STREAMER =================================================================================
+
+/// Generates chunks of columns on background threads and grafts them into the world
+/// a node of `chunk_size` at a time, so scrolling the world never blocks a frame on generation.
+pub struct WorldStreamer {
+    /// Time spent grafting chunks into the world by every `WorldStreamer::update`,
+    /// at least one node of a ready chunk is always grafted.
+    pub budget: Duration,
+    chunk_size: i32,
+
+    requests: Sender<IVec3>,
+    /// Generated chunks, `None` if the generation panicked.
+    results: Receiver<(IVec3, Option<World>)>,
+
+    /// Chunks being generated.
+    requested: HashSet<IVec3>,
+    /// Chunks generated but not grafted yet.
+    ready: HashMap<IVec3, World>,
+    /// Chunks of the world still waiting for their voxels, nearest to the player first.
+    missing: VecDeque<IVec3>,
+    /// The chunk being grafted, with the height of its next node.
+    grafting: Option<(IVec3, World, i32)>,
+}
+
+impl WorldStreamer {
+    pub fn new(world_gen: Arc<WorldGen>, world: &World, threads: usize) -> Self {
+        let chunk_size = CHUNK_SIZE.min(world.size as i32);
+        let max_depth = world.max_depth;
+        let (requests, request_receiver) = channel::<IVec3>();
+        let (result_sender, results) = channel();
+        let request_receiver = Arc::new(Mutex::new(request_receiver));
+
+        for i in 0..threads.max(1) {
+            let world_gen = Arc::clone(&world_gen);
+            let request_receiver = Arc::clone(&request_receiver);
+            let result_sender = result_sender.clone();
+            thread::Builder::new()
+                .name(format!("World Streaming {i}"))
+                .spawn(move || {
+                    loop {
+                        let Ok(chunk_min) = request_receiver.lock().unwrap().recv() else {
+                            break;
+                        };
+                        let chunk = catch_unwind(AssertUnwindSafe(|| {
+                            world_gen.generate_chunk(chunk_min, chunk_size, max_depth)
+                        }));
+                        if result_sender.send((chunk_min, chunk.ok())).is_err() {
+                            break;
+                        }
+                    }
+                })
+                .unwrap();
+        }
+
+        Self {
+            budget: STREAMING_BUDGET,
+            chunk_size,
+            requests,
+            results,
+            requested: HashSet::new(),
+            ready: HashMap::new(),
+            missing: VecDeque::new(),
+            grafting: None,
+        }
+    }
+
+    /// Starts generating the chunks of the region going from `min` to `max` (exclusive),
+    /// nearest to `focus` first, so they are ready once the region enters the world.
+    pub fn prefetch(&mut self, min: IVec3, max: IVec3, focus: Vec3) {
+        for chunk_min in self.chunks(min, max, focus) {
+            self.request(chunk_min);
+        }
+    }
+
+    /// Marks the chunks of the region going from `min` to `max` (exclusive) as missing from
+    /// the world, they are grafted by the next updates as soon as they are generated.
+    pub fn require(&mut self, min: IVec3, max: IVec3, focus: Vec3) {
+        for chunk_min in self.chunks(min, max, focus) {
+            self.request(chunk_min);
+            if !self.missing.contains(&chunk_min) {
+                self.missing.push_back(chunk_min);
+            }
+        }
+    }
+
+    /// Whether some chunks of the world are still waiting for their voxels.
+    pub fn is_streaming(&self) -> bool {
+        !self.missing.is_empty() || self.grafting.is_some()
+    }
+
+    /// Collects the generated chunks and grafts the missing ones until the budget runs out,
+    /// checking it after every node grafted.
+    ///
+    /// If the world runs out of nodes the chunks still missing are given up, the world keeps
+    /// the ones grafted so far. The chunks whose generation panicked are given up too.
+    pub fn update(&mut self, world: &mut World) -> Result<(), WorldErr> {
+        while let Ok((chunk_min, chunk)) = self.results.try_recv() {
+            self.requested.remove(&chunk_min);
+            if let Some(chunk) = chunk {
+                self.ready.insert(chunk_min, chunk);
+            } else {
+                log::error!("Generating the chunk at {chunk_min} panicked, it's left empty");
+                self.missing.retain(|&missing| missing != chunk_min);
+            }
+        }
+
+        // Chunks that scrolled out of the world are no longer missing, the prefetched ones
+        // are kept until they get farther than half the world.
+        let (min, max) = (world.min(), world.max());
+        let inside = |chunk: &IVec3, margin: i32| {
+            chunk.x >= min.x - margin
+                && chunk.z >= min.z - margin
+                && chunk.x < max.x + margin
+                && chunk.z < max.z + margin
+        };
+        self.missing.retain(|chunk| inside(chunk, 0));
+        if self
+            .grafting
+            .as_ref()
+            .is_some_and(|(chunk_min, ..)| !inside(chunk_min, 0))
+        {
+            self.grafting = None;
+        }
+        let missing = &self.missing;
+        let margin = world.size as i32 / 2;
+        self.ready.retain(|chunk, _| {
+            missing.contains(chunk) || !inside(chunk, 0) && inside(chunk, margin)
+        });
+
+        let start = Instant::now();
+        let mut i = 0;
+        loop {
+            let Some((chunk_min, chunk, y)) = &mut self.grafting else {
+                // The next missing chunk that is ready.
+                let Some(&chunk_min) = self.missing.get(i) else {
+                    break;
+                };
+                if let Some(chunk) = self.ready.remove(&chunk_min) {
+                    self.missing.remove(i);
+                    self.grafting = Some((chunk_min, chunk, world.min().y));
+                } else {
+                    self.request(chunk_min);
+                    i += 1;
+                }
+                continue;
+            };
+
+            let size = self.chunk_size;
+            if let Err(e) = world.graft(chunk_min.with_y(*y), size as u32, chunk) {
+                self.missing.clear();
+                self.grafting = None;
+                return Err(e);
+            }
+            *y += size;
+            if *y >= world.max().y {
+                self.grafting = None;
+            }
+            if start.elapsed() >= self.budget {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn request(&mut self, chunk_min: IVec3) {
+        if self.ready.contains_key(&chunk_min) || !self.requested.insert(chunk_min) {
+            return;
+        }
+        // The chunk stays missing, and it's requested again by the next update.
+        if self.requests.send(chunk_min).is_err() {
+            log::error!("No worker left to generate the chunk at {chunk_min}");
+            self.requested.remove(&chunk_min);
+        }
+    }
+
+    /// The chunks of the region, sorted by their distance from `focus`.
+    fn chunks(&self, min: IVec3, max: IVec3, focus: Vec3) -> Vec<IVec3> {
+        let size = self.chunk_size;
+        let mut chunks = (min.x..max.x)
+            .step_by(size as usize)
+            .flat_map(|x| {
+                (min.z..max.z)
+                    .step_by(size as usize)
+                    .map(move |z| ivec3(x, min.y, z))
+            })
+            .collect::<Vec<_>>();
+
+        let dist = |chunk: &IVec3| {
+            let center = chunk.as_vec3() + Vec3::splat(size as f32 * 0.5);
+            (center - focus).with_y(0.0).length_squared()
+        };
+        chunks.sort_by(|a, b| dist(a).total_cmp(&dist(b)));
+        chunks
+    }
+}
+
+//= TESTS ==========================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::BiomeRegistry;
+
+    #[test]
+    fn streamed_chunks_match_generated_world() {
+        let biomes = || BiomeRegistry::from_config(&Default::default()).unwrap();
+        let world_gen = Arc::new(WorldGen::new(9, biomes()));
+        let mut world = World::new_region(ivec3(-128, 0, 0), 7);
+        let mut streamer = WorldStreamer::new(Arc::clone(&world_gen), &world, 2);
+        streamer.budget = Duration::ZERO;
+        streamer.require(world.min(), world.max(), Vec3::ZERO);
+
+        // A zero budget grafts a single node per update, leaving the columns half grafted.
+        let mut half_grafted = false;
+        let deadline = Instant::now() + Duration::from_secs(120);
+        while streamer.is_streaming() {
+            assert!(Instant::now() < deadline, "the chunks are never grafted");
+            streamer.update(&mut world).unwrap();
+            half_grafted |= streamer.grafting.is_some();
+            thread::yield_now();
+        }
+        assert!(half_grafted);
+
+        let mut expected = World::new_region(world.min(), 7);
+        world_gen
+            .populate_serial(expected.min(), expected.max(), &mut expected)
+            .unwrap();
+        for x in 0..128 {
+            for y in 0..128 {
+                for z in 0..128 {
+                    let pos = world.min() + ivec3(x, y, z);
+                    assert!(
+                        world.get_voxel(pos).unwrap() == expected.get_voxel(pos).unwrap(),
+                        "{pos}"
+                    );
+                }
+            }
+        }
+    }
+}
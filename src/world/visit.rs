@@ -0,0 +1,101 @@
+//= IMPORTS ========================================================================================
+
+use crate::world::World;
+
+use voxel_render::Voxel;
+
+use glam::{IVec3, ivec3};
+
+//= LEAVES =========================================================================================
+
+/// A leaf of the SVO, a cube of voxels all of the same type.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Leaf {
+    pub min: IVec3,
+    pub size: u32,
+    pub voxel: Voxel,
+}
+
+impl Leaf {
+    #[must_use]
+    pub fn max(&self) -> IVec3 {
+        self.min + IVec3::splat(self.size as i32)
+    }
+}
+
+/// Iterator over every leaf of a world, air included, see `World::leaves`.
+pub struct Leaves<'a> {
+    world: &'a World,
+    /// Nodes left to visit, with their min corner and size.
+    stack: Vec<(u32, IVec3, u32)>,
+}
+
+impl Iterator for Leaves<'_> {
+    type Item = Leaf;
+
+    fn next(&mut self) -> Option<Leaf> {
+        while let Some((idx, min, size)) = self.stack.pop() {
+            let node = self.world.get_node(idx);
+            if !node.is_split() {
+                return Some(Leaf {
+                    min,
+                    size,
+                    voxel: node.get_voxel(),
+                });
+            }
+            let half = size / 2;
+            for i in (0..8).rev() {
+                self.stack
+                    .push((node.get_child(i), child_min(min, half, i), half));
+            }
+        }
+        None
+    }
+}
+
+//= VISIT ==========================================================================================
+
+/// Walk the leaves of the SVO, in time proportional to the nodes visited
+/// rather than to the voxels they contain.
+impl World {
+    /// Every leaf of the world, depth first, uniform nodes are never expanded.
+    #[must_use]
+    pub fn leaves(&self) -> Leaves<'_> {
+        Leaves {
+            world: self,
+            stack: vec![(0, self.min, self.size)],
+        }
+    }
+
+    /// Calls `f` for every leaf overlapping the box going from `min` to `max` (exclusive),
+    /// depth first. The leaves crossing the box border are given whole.
+    pub fn visit_region(&self, min: IVec3, max: IVec3, mut f: impl FnMut(Leaf)) {
+        let mut stack = vec![(0, self.min, self.size)];
+        while let Some((idx, node_min, size)) = stack.pop() {
+            let node_max = node_min + IVec3::splat(size as i32);
+            if node_max.cmple(min).any() || node_min.cmpge(max).any() {
+                continue;
+            }
+            let node = self.get_node(idx);
+            if !node.is_split() {
+                f(Leaf {
+                    min: node_min,
+                    size,
+                    voxel: node.get_voxel(),
+                });
+                continue;
+            }
+            let half = size / 2;
+            for i in (0..8).rev() {
+                stack.push((node.get_child(i), child_min(node_min, half, i), half));
+            }
+        }
+    }
+}
+
+//= HELPERS ========================================================================================
+
+/// The min corner of the child `i` of a node, whose children are `half` in size.
+fn child_min(min: IVec3, half: u32, i: u32) -> IVec3 {
+    min + ivec3(i as i32 & 1, i as i32 >> 1 & 1, i as i32 >> 2) * half as i32
+}
@@ -0,0 +1,407 @@
+//= IMPORTS ========================================================================================
+
+use crate::world::{NodeSeq, PasteMode, Reader, Schematic, World, WorldErr};
+
+use voxel_render::{Material, Voxel};
+
+use glam::{IVec3, ivec3};
+use hashbrown::HashMap;
+
+use std::fs::{read, write};
+
+//= CONSTANTS ======================================================================================
+
+const MAGIC: [u8; 4] = *b"VOX ";
+
+/// Version written by `Schematic::to_vox`, the one of MagicaVoxel 0.99.
+const VOX_VERSION: u32 = 150;
+
+/// Models can't be bigger than this along any axis, the coordinates are bytes.
+pub const MAX_VOX_SIZE: i32 = 256;
+
+//= MAGICAVOXEL FILES ==============================================================================
+
+/// Read and write MagicaVoxel `.vox` files.
+///
+/// Only the first model of a file is read, from its `SIZE`, `XYZI` and `RGBA` chunks,
+/// all the other chunks (scene graph, materials, layers...) are skipped.
+///
+/// MagicaVoxel is Z-up, so a model voxel at `(x, y, z)` goes to `(x, z, size.y - 1 - y)`
+/// of the schematic, which keeps the model handedness.
+///
+/// Palette colors are mapped to the voxel whose `Material` has the nearest color,
+/// files without a `RGBA` chunk use the default palette of MagicaVoxel.
+/// Exported files use the voxel ids as color indices when they all fit in a byte, so they are
+/// imported back exactly, otherwise the palette lists the voxels used, up to 255 of them.
+impl Schematic {
+    pub fn from_vox(bytes: &[u8], materials: &[Material]) -> Result<Self, WorldErr> {
+        if bytes.len() < 8 || bytes[0..4] != MAGIC {
+            return Err(WorldErr::InvalidFormat);
+        }
+        let mut reader = Reader(&bytes[8..]);
+
+        // The MAIN chunk has no content, all the others are its children.
+        let (id, content) = read_chunk(&mut reader)?;
+        if id != *b"MAIN" || !content.is_empty() {
+            return Err(WorldErr::InvalidFormat);
+        }
+
+        let mut size = None;
+        let mut xyzi = None;
+        let mut rgba = None;
+        while !reader.0.is_empty() {
+            match read_chunk(&mut reader)? {
+                (id, content) if id == *b"SIZE" && size.is_none() => size = Some(content),
+                (id, content) if id == *b"XYZI" && xyzi.is_none() => xyzi = Some(content),
+                (id, content) if id == *b"RGBA" => rgba = Some(content),
+                _ => {}
+            }
+        }
+
+        let mut size_reader = Reader(size.ok_or(WorldErr::InvalidFormat)?);
+        let vox_size = read_ivec3(&mut size_reader)?;
+        if vox_size.cmplt(IVec3::ONE).any() || vox_size.cmpgt(IVec3::splat(MAX_VOX_SIZE)).any() {
+            return Err(WorldErr::InvalidFormat);
+        }
+        let default_rgba;
+        let rgba = match rgba {
+            Some(rgba) => rgba,
+            None => {
+                default_rgba = default_palette();
+                &default_rgba
+            }
+        };
+        let palette = palette_voxels(materials, rgba)?;
+
+        let size = ivec3(vox_size.x, vox_size.z, vox_size.y);
+        let mut schematic = Self::filled(size, Voxel::AIR);
+        let mut xyzi = Reader(xyzi.ok_or(WorldErr::InvalidFormat)?);
+        let count = read_u32(&mut xyzi)? as usize;
+        if xyzi.0.len() != count * 4 {
+            return Err(WorldErr::InvalidFormat);
+        }
+        for _ in 0..count {
+            let [x, y, z, color] = xyzi.take();
+            let vox_pos = ivec3(i32::from(x), i32::from(y), i32::from(z));
+            if color == 0 || vox_pos.cmpge(vox_size).any() {
+                return Err(WorldErr::InvalidFormat);
+            }
+            let voxel = palette[color as usize];
+            let pos = ivec3(vox_pos.x, vox_pos.z, size.z - 1 - vox_pos.y);
+            schematic.set(pos, voxel);
+        }
+        Ok(schematic)
+    }
+
+    /// Writes the schematic as a single model, its air voxels are left empty.
+    ///
+    /// Fails with `WorldErr::OutOfBounds` if the schematic is bigger than `MAX_VOX_SIZE`
+    /// or it has more voxel types than the palette can hold.
+    pub fn to_vox(&self, materials: &[Material]) -> Result<Vec<u8>, WorldErr> {
+        let size = self.size();
+        if size.cmpgt(IVec3::splat(MAX_VOX_SIZE)).any() {
+            return Err(WorldErr::OutOfBounds);
+        }
+
+        // The voxel of every color index, and the color index of every voxel.
+        let mut palette: Vec<Voxel> = (0..=255).map(Voxel).collect();
+        let mut indices: HashMap<u16, u8> = (0..=255).map(|idx| (idx, idx as u8)).collect();
+        if self.voxels().any(|(_, voxel)| voxel.0 > 255) {
+            palette = vec![Voxel::AIR];
+            indices.clear();
+            for (_, voxel) in self.voxels() {
+                if !indices.contains_key(&voxel.0) && voxel != Voxel::AIR {
+                    indices.insert(voxel.0, palette.len() as u8);
+                    palette.push(voxel);
+                    if palette.len() > 256 {
+                        return Err(WorldErr::OutOfBounds);
+                    }
+                }
+            }
+        }
+
+        let mut xyzi = vec![];
+        let mut count = 0u32;
+        for (pos, voxel) in self.voxels() {
+            if voxel == Voxel::AIR {
+                continue;
+            }
+            let vox_pos = ivec3(pos.x, size.z - 1 - pos.z, pos.y);
+            xyzi.extend(vox_pos.to_array().map(|c| c as u8));
+            xyzi.push(indices[&voxel.0]);
+            count += 1;
+        }
+
+        // The color index `i` is stored at `i - 1`, index 0 means empty.
+        let mut rgba = Vec::with_capacity(256 * 4);
+        for idx in 1..=256 {
+            let voxel = palette.get(idx).map_or(0, |voxel| voxel.0 as usize);
+            let color = materials
+                .get(voxel)
+                .filter(|_| idx < palette.len())
+                .map_or([0.0; 3], |material| material.color);
+            rgba.extend(color.map(to_byte));
+            rgba.push(255);
+        }
+
+        let mut children = vec![];
+        let size_content = [size.x, size.z, size.y].map(|c| (c as u32).to_le_bytes());
+        write_chunk(&mut children, *b"SIZE", &size_content.concat());
+        write_chunk(
+            &mut children,
+            *b"XYZI",
+            &[&count.to_le_bytes()[..], &xyzi].concat(),
+        );
+        write_chunk(&mut children, *b"RGBA", &rgba);
+
+        let mut bytes = Vec::with_capacity(8 + 12 + children.len());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&VOX_VERSION.to_le_bytes());
+        bytes.extend_from_slice(b"MAIN");
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&(children.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&children);
+        Ok(bytes)
+    }
+
+    pub fn load_vox(filename: &str, materials: &[Material]) -> Result<Self, WorldErr> {
+        let bytes = read(filename).map_err(|e| WorldErr::Io(format!("{e}: {filename}")))?;
+        Self::from_vox(&bytes, materials)
+    }
+
+    pub fn save_vox(&self, filename: &str, materials: &[Material]) -> Result<(), WorldErr> {
+        let bytes = self.to_vox(materials)?;
+        write(filename, bytes).map_err(|e| WorldErr::Io(format!("{e}: {filename}")))
+    }
+}
+
+impl World {
+    /// Pastes the model of a `.vox` file with its min corner at `pos`,
+    /// the empty voxels of the model leave the world untouched. Returns the modified nodes.
+    pub fn import_vox(
+        &mut self,
+        filename: &str,
+        pos: IVec3,
+        materials: &[Material],
+    ) -> Result<Vec<NodeSeq>, WorldErr> {
+        let schematic = Schematic::load_vox(filename, materials)?;
+        schematic.paste(self, pos, PasteMode::IgnoreAir)
+    }
+
+    /// Saves the box going from `a` to `b`, both inclusive and in any order, as a `.vox` file.
+    pub fn export_vox(
+        &self,
+        filename: &str,
+        a: IVec3,
+        b: IVec3,
+        materials: &[Material],
+    ) -> Result<(), WorldErr> {
+        Schematic::capture(self, a, b)?.save_vox(filename, materials)
+    }
+}
+
+//= HELPERS ========================================================================================
+
+fn read_u32(reader: &mut Reader) -> Result<u32, WorldErr> {
+    if reader.0.len() < 4 {
+        return Err(WorldErr::InvalidFormat);
+    }
+    Ok(u32::from_le_bytes(reader.take()))
+}
+
+fn read_ivec3(reader: &mut Reader) -> Result<IVec3, WorldErr> {
+    let mut c = [0; 3];
+    for c in &mut c {
+        *c = read_u32(reader)? as i32;
+    }
+    Ok(IVec3::from_array(c))
+}
+
+/// Returns the id and the content of the next chunk, its children follow it in the reader.
+fn read_chunk<'a>(reader: &mut Reader<'a>) -> Result<([u8; 4], &'a [u8]), WorldErr> {
+    if reader.0.len() < 12 {
+        return Err(WorldErr::InvalidFormat);
+    }
+    let id = reader.take();
+    let content_len = read_u32(reader)? as usize;
+    read_u32(reader)?;
+    if reader.0.len() < content_len {
+        return Err(WorldErr::InvalidFormat);
+    }
+    let (content, rest) = reader.0.split_at(content_len);
+    reader.0 = rest;
+    Ok((id, content))
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, id: [u8; 4], content: &[u8]) {
+    bytes.extend_from_slice(&id);
+    bytes.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    bytes.extend_from_slice(content);
+}
+
+/// Maps the content of a `RGBA` chunk to the voxel of every color index.
+fn palette_voxels(materials: &[Material], rgba: &[u8]) -> Result<Vec<Voxel>, WorldErr> {
+    if rgba.len() != 256 * 4 {
+        return Err(WorldErr::InvalidFormat);
+    }
+    let mut voxels = vec![Voxel::AIR];
+    for (idx, color) in rgba.chunks_exact(4).take(255).enumerate() {
+        let color = [color[0], color[1], color[2]];
+        voxels.push(nearest_voxel(materials, idx + 1, color));
+    }
+    Ok(voxels)
+}
+
+/// The `RGBA` chunk of the MagicaVoxel default palette: a 6x6x6 color cube without black,
+/// then ramps of red, green, blue and gray.
+fn default_palette() -> Vec<u8> {
+    const CUBE: [u8; 6] = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    const RAMP: [u8; 10] = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+
+    let mut colors = Vec::with_capacity(256);
+    for r in CUBE {
+        for g in CUBE {
+            colors.extend(CUBE.map(|b| [r, g, b]));
+        }
+    }
+    colors.pop();
+    colors.extend(RAMP.map(|c| [c, 0, 0]));
+    colors.extend(RAMP.map(|c| [0, c, 0]));
+    colors.extend(RAMP.map(|c| [0, 0, c]));
+    colors.extend(RAMP.map(|c| [c, c, c]));
+    // The color index `i` is stored at `i - 1`, the last entry is unused.
+    colors.push([0, 0, 0]);
+    colors
+        .iter()
+        .flat_map(|&[r, g, b]| [r, g, b, 255])
+        .collect()
+}
+
+/// The voxel with the material color nearest to `color`, preferring the voxel with the same
+/// id of the color index if its color matches.
+fn nearest_voxel(materials: &[Material], idx: usize, color: [u8; 3]) -> Voxel {
+    if materials
+        .get(idx)
+        .is_some_and(|m| m.empty == 0 && m.color.map(to_byte) == color)
+    {
+        return Voxel(idx as u16);
+    }
+    let dist = |material: &Material| {
+        let d = [0, 1, 2].map(|i| material.color[i] - f32::from(color[i]) / 255.0);
+        d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
+    };
+    materials
+        .iter()
+        .enumerate()
+        .filter(|(_, material)| material.empty == 0)
+        .min_by(|(_, a), (_, b)| dist(a).total_cmp(&dist(b)))
+        .map_or(Voxel::STONE, |(idx, _)| Voxel(idx as u16))
+}
+
+fn to_byte(c: f32) -> u8 {
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+//= TESTS ==========================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::WorldGen;
+
+    use voxel_render::VOXEL_MATERIALS;
+
+    use std::env::temp_dir;
+
+    #[test]
+    fn generated_region_round_trips() {
+        let biomes = crate::world::BiomeRegistry::from_config(&Default::default()).unwrap();
+        let mut world = World::new_region(IVec3::ZERO, 7);
+        WorldGen::new(11, biomes)
+            .populate(IVec3::ZERO, IVec3::splat(128), &mut world)
+            .unwrap();
+
+        let filepath = temp_dir().join("vrt_round_trip.vox");
+        let filename = filepath.to_str().unwrap();
+        let (a, b) = (ivec3(10, 20, 30), ivec3(73, 120, 90));
+        world.export_vox(filename, a, b, VOXEL_MATERIALS).unwrap();
+
+        let offset = ivec3(5, 0, 7);
+        let mut copy = World::new_region(IVec3::ZERO, 7);
+        copy.import_vox(filename, offset, VOXEL_MATERIALS).unwrap();
+        std::fs::remove_file(&filepath).unwrap();
+
+        for x in a.x..=b.x {
+            for y in a.y..=b.y {
+                for z in a.z..=b.z {
+                    let pos = ivec3(x, y, z);
+                    let copied = copy.get_voxel(pos - a + offset).unwrap();
+                    assert!(world.get_voxel(pos).unwrap() == copied, "{pos}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn foreign_palette_maps_to_nearest_material() {
+        // A 2x3x4 model with a voxel in two opposite corners and a custom palette.
+        let mut rgba = vec![0; 256 * 4];
+        rgba[0..4].copy_from_slice(&[250, 236, 140, 255]); // Sand like
+        rgba[4..8].copy_from_slice(&[100, 101, 99, 255]); // Stone like
+
+        let mut children = vec![];
+        let size = [2u32, 3, 4].map(u32::to_le_bytes).concat();
+        write_chunk(&mut children, *b"SIZE", &size);
+        write_chunk(
+            &mut children,
+            *b"XYZI",
+            &[2, 0, 0, 0, 0, 0, 0, 1, 1, 2, 3, 2],
+        );
+        write_chunk(&mut children, *b"nTRN", &[1, 2, 3]);
+        write_chunk(&mut children, *b"RGBA", &rgba);
+
+        let mut bytes = [&MAGIC[..], &VOX_VERSION.to_le_bytes(), b"MAIN"].concat();
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&(children.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&children);
+
+        let schematic = Schematic::from_vox(&bytes, VOXEL_MATERIALS).unwrap();
+        assert_eq!(schematic.size(), ivec3(2, 4, 3));
+        assert!(schematic.get(ivec3(0, 0, 2)) == Voxel::SAND);
+        assert!(schematic.get(ivec3(1, 3, 0)) == Voxel::STONE);
+        let solid = schematic.voxels().filter(|(_, v)| *v != Voxel::AIR).count();
+        assert_eq!(solid, 2);
+    }
+
+    #[test]
+    fn missing_palette_uses_the_default_one() {
+        // A 1x1x3 model using the first color of the cube, of the red ramp and of the gray ramp.
+        let mut children = vec![];
+        let size = [1u32, 1, 3].map(u32::to_le_bytes).concat();
+        write_chunk(&mut children, *b"SIZE", &size);
+        write_chunk(
+            &mut children,
+            *b"XYZI",
+            &[3, 0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 216, 0, 0, 2, 246],
+        );
+
+        let mut bytes = [&MAGIC[..], &VOX_VERSION.to_le_bytes(), b"MAIN"].concat();
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&(children.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&children);
+
+        let schematic = Schematic::from_vox(&bytes, VOXEL_MATERIALS).unwrap();
+        assert_eq!(schematic.size(), ivec3(1, 3, 1));
+        let expected = [
+            (1, [255, 255, 255]),
+            (216, [238, 0, 0]),
+            (246, [238, 238, 238]),
+        ];
+        for (y, (idx, color)) in expected.into_iter().enumerate() {
+            let voxel = schematic.get(ivec3(0, y as i32, 0));
+            assert!(voxel == nearest_voxel(VOXEL_MATERIALS, idx, color), "{idx}");
+            assert_eq!(VOXEL_MATERIALS[voxel.0 as usize].empty, 0, "{idx}");
+        }
+    }
+}